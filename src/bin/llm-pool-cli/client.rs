@@ -0,0 +1,294 @@
+use anyhow::{Context, Result};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use hmac::{Hmac, Mac};
+use prost::Message as _;
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::HashMap;
+use std::time::Duration;
+use tonic::transport::Channel;
+
+pub mod proto {
+    tonic::include_proto!("vvtv.llmpool.v1");
+}
+
+use proto::llm_pool_client::LlmPoolClient;
+
+/// One request, in the same shape as the `POST /v1/infer` body, so JSONL
+/// files from the HTTP API, the cache warm-up or batch jobs replay as-is.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct RequestLine {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tenant_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub project_id: Option<String>,
+    pub task: String,
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub prompt: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub messages: Vec<ChatMessage>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub deadline_ms: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub strategy: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub images: Vec<String>,
+    #[serde(flatten)]
+    pub sampling: Sampling,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Sampling {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<i32>,
+}
+
+/// What the CLI reports about an answer, whichever transport carried it.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct Reply {
+    pub request_id: String,
+    pub content: String,
+    pub winner_model: String,
+    pub duration_ms: i32,
+    #[serde(default)]
+    pub queue_ms: i32,
+    pub from_cache: bool,
+    pub strategy_used: String,
+    #[serde(default)]
+    pub meta: HashMap<String, String>,
+}
+
+/// A failed call: `kind` is the HTTP status or gRPC code, for grouping.
+#[derive(Debug, Clone)]
+pub struct CallError {
+    pub kind: String,
+    pub message: String,
+}
+
+/// Request signing with a tenant key and secret: `X-Signature` is the hex
+/// HMAC-SHA256 of `"{timestamp}\n{nonce}\n{body}"`. The server ignores
+/// these headers until HMAC authentication lands in `security::hmac`.
+#[derive(Debug, Clone)]
+pub struct Credentials {
+    pub api_key: String,
+    pub secret: String,
+}
+
+impl Credentials {
+    fn headers(&self, body: &[u8]) -> [(&'static str, String); 4] {
+        let timestamp = chrono::Utc::now().timestamp().to_string();
+        let nonce = uuid::Uuid::new_v4().to_string();
+        let mut mac = Hmac::<Sha256>::new_from_slice(self.secret.as_bytes())
+            .expect("HMAC accepts keys of any length");
+        mac.update(timestamp.as_bytes());
+        mac.update(b"\n");
+        mac.update(nonce.as_bytes());
+        mac.update(b"\n");
+        mac.update(body);
+        let signature = mac.finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+        [
+            ("x-api-key", self.api_key.clone()),
+            ("x-timestamp", timestamp),
+            ("x-nonce", nonce),
+            ("x-signature", signature),
+        ]
+    }
+}
+
+#[derive(Clone)]
+enum Transport {
+    Http { client: reqwest::Client, url: String },
+    Grpc(LlmPoolClient<Channel>),
+}
+
+#[derive(Clone)]
+pub struct Client {
+    transport: Transport,
+    credentials: Option<Credentials>,
+    timeout: Duration,
+}
+
+impl Client {
+    pub async fn http(url: &str, credentials: Option<Credentials>, timeout: Duration) -> Result<Self> {
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            transport: Transport::Http { client, url: url.trim_end_matches('/').to_string() },
+            credentials,
+            timeout,
+        })
+    }
+    
+    pub async fn grpc(url: &str, credentials: Option<Credentials>, timeout: Duration) -> Result<Self> {
+        let client = LlmPoolClient::connect(url.to_string())
+            .await
+            .with_context(|| format!("Cannot connect to {}", url))?;
+        Ok(Self { transport: Transport::Grpc(client), credentials, timeout })
+    }
+    
+    pub async fn infer(&self, request: &RequestLine) -> std::result::Result<Reply, CallError> {
+        match &self.transport {
+            Transport::Http { client, url } => self.infer_http(client, url, request).await,
+            Transport::Grpc(client) => self.infer_grpc(client.clone(), request).await,
+        }
+    }
+    
+    async fn infer_http(
+        &self,
+        client: &reqwest::Client,
+        url: &str,
+        request: &RequestLine,
+    ) -> std::result::Result<Reply, CallError> {
+        let body = serde_json::to_vec(request).map_err(|e| CallError::new("encode", e))?;
+        let mut call = client
+            .post(format!("{}/v1/infer", url))
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+        if let Some(credentials) = &self.credentials {
+            for (name, value) in credentials.headers(&body) {
+                call = call.header(name, value);
+            }
+        }
+        
+        let response = call.body(body).send().await.map_err(|e| {
+            CallError::new(if e.is_timeout() { "timeout" } else { "connect" }, e)
+        })?;
+        let status = response.status();
+        if !status.is_success() {
+            let message = response.text().await.unwrap_or_default();
+            return Err(CallError { kind: format!("http {}", status.as_u16()), message });
+        }
+        response.json().await.map_err(|e| CallError::new("decode", e))
+    }
+    
+    async fn infer_grpc(
+        &self,
+        mut client: LlmPoolClient<Channel>,
+        request: &RequestLine,
+    ) -> std::result::Result<Reply, CallError> {
+        let query = to_query(request).map_err(|e| CallError::new("encode", e))?;
+        let headers = self.credentials.as_ref().map(|c| c.headers(&query.encode_to_vec()));
+        
+        let mut call = tonic::Request::new(query);
+        call.set_timeout(self.timeout);
+        for (name, value) in headers.into_iter().flatten() {
+            if let Ok(value) = value.parse() {
+                call.metadata_mut().insert(name, value);
+            }
+        }
+        
+        let answer = client.infer(call)
+            .await
+            .map_err(|status| CallError {
+                kind: format!("grpc {:?}", status.code()),
+                message: status.message().to_string(),
+            })?
+            .into_inner();
+        // The HTTP API reports cache hits as CACHE, which has no proto value
+        let strategy_used = match answer.decision.as_ref().map(|d| d.strategy_used()) {
+            Some(proto::Strategy::Unspecified) | None if answer.from_cache => "CACHE".to_string(),
+            Some(strategy) => strategy_name(strategy),
+            None => String::new(),
+        };
+        Ok(Reply {
+            request_id: answer.request_id,
+            content: answer.content,
+            winner_model: answer.winner_model,
+            duration_ms: answer.duration_ms,
+            queue_ms: answer.queue_ms,
+            from_cache: answer.from_cache,
+            strategy_used,
+            meta: answer.meta,
+        })
+    }
+}
+
+impl CallError {
+    fn new(kind: &str, err: impl std::fmt::Display) -> Self {
+        Self { kind: kind.to_string(), message: err.to_string() }
+    }
+}
+
+fn to_query(request: &RequestLine) -> Result<proto::Query> {
+    let images = request.images.iter()
+        .map(|image| BASE64.decode(image))
+        .collect::<std::result::Result<Vec<_>, _>>()
+        .context("images must be base64")?;
+    let strategy = match request.strategy.as_deref().map(str::to_ascii_uppercase).as_deref() {
+        None | Some("") => proto::Strategy::Unspecified,
+        Some(name) => proto::Strategy::from_str_name(&format!("STRATEGY_{}", name))
+            .with_context(|| format!("Unknown strategy: {}", name))?,
+    };
+    let priority = match request.priority.as_deref().map(str::to_ascii_uppercase).as_deref() {
+        None | Some("") => proto::Priority::Unspecified,
+        Some(name) => proto::Priority::from_str_name(&format!("PRIORITY_{}", name))
+            .with_context(|| format!("Unknown priority: {}", name))?,
+    };
+    let sampling = &request.sampling;
+    
+    Ok(proto::Query {
+        request_id: request.request_id.clone().unwrap_or_default(),
+        tenant_id: request.tenant_id.clone().unwrap_or_default(),
+        project_id: request.project_id.clone().unwrap_or_default(),
+        task_name: request.task.clone(),
+        prompt: request.prompt.clone(),
+        messages: request.messages.iter()
+            .map(|m| proto::Message { role: m.role.clone(), content: m.content.clone() })
+            .collect(),
+        input_json: request.input.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+        output_schema_json: request.output_schema.as_ref().map(|v| v.to_string()).unwrap_or_default(),
+        max_tokens: request.max_tokens.unwrap_or(0),
+        deadline_ms: request.deadline_ms.unwrap_or(0),
+        strategy: strategy as i32,
+        priority: priority as i32,
+        images,
+        sampling: Some(proto::Sampling {
+            temperature: sampling.temperature,
+            top_p: sampling.top_p,
+            top_k: sampling.top_k,
+            seed: sampling.seed,
+            stop: sampling.stop.clone(),
+            repeat_penalty: sampling.repeat_penalty,
+            num_ctx: sampling.num_ctx,
+        }),
+        ..Default::default()
+    })
+}
+
+/// "VOTING" rather than "STRATEGY_VOTING", to match the HTTP API.
+fn strategy_name(strategy: proto::Strategy) -> String {
+    strategy.as_str_name().trim_start_matches("STRATEGY_").to_string()
+}
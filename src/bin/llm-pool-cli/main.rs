@@ -0,0 +1,216 @@
+mod client;
+mod replay;
+
+use anyhow::{bail, Context, Result};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use client::{Client, Credentials, RequestLine};
+use std::io::Read;
+use std::time::{Duration, Instant};
+
+/// Command-line client for the LLM pool: single requests and JSONL replays.
+#[derive(Parser)]
+#[command(name = "llm-pool-cli", version)]
+struct Cli {
+    #[arg(long, value_enum, default_value_t = Protocol::Http, global = true)]
+    protocol: Protocol,
+    /// Pool address; defaults to http://localhost:7071 (HTTP) or
+    /// http://localhost:7070 (gRPC)
+    #[arg(long, env = "LLM_POOL_URL", global = true)]
+    url: Option<String>,
+    /// Tenant API key, sent as X-Api-Key
+    #[arg(long, env = "LLM_POOL_API_KEY", global = true)]
+    api_key: Option<String>,
+    /// Signing secret; with --api-key, requests carry an HMAC X-Signature.
+    /// The pool does not verify signatures yet, so this only matters to a
+    /// gateway in front of it that does
+    #[arg(long, env = "LLM_POOL_SECRET", global = true, hide_env_values = true)]
+    secret: Option<String>,
+    /// Client-side timeout per request
+    #[arg(long, default_value_t = 30000, global = true)]
+    timeout_ms: u64,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Protocol {
+    Http,
+    Grpc,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Send one request and print the answer
+    Infer(InferArgs),
+    /// Send every request of a JSONL file and report latency, errors, cache
+    /// hits and winners per task and strategy
+    Replay(ReplayArgs),
+}
+
+/// Settings applied to every request, over what the file or flags say.
+#[derive(Args)]
+struct Overrides {
+    /// FASTEST, VOTING, WEIGHTED, CONSENSUS or JUDGE
+    #[arg(long)]
+    strategy: Option<String>,
+    #[arg(long)]
+    deadline_ms: Option<i32>,
+    #[arg(long)]
+    tenant: Option<String>,
+    /// realtime, normal or batch
+    #[arg(long)]
+    priority: Option<String>,
+}
+
+#[derive(Args)]
+struct InferArgs {
+    #[arg(long)]
+    task: String,
+    /// Prompt text; "-" reads it from stdin
+    #[arg(long, conflicts_with = "input")]
+    prompt: Option<String>,
+    /// Structured task input as JSON, rendered by the task template
+    #[arg(long)]
+    input: Option<String>,
+    #[arg(long)]
+    max_tokens: Option<i32>,
+    #[command(flatten)]
+    overrides: Overrides,
+    /// Print the whole reply as JSON instead of just the content
+    #[arg(long)]
+    json: bool,
+}
+
+#[derive(Args)]
+struct ReplayArgs {
+    /// JSONL file of /v1/infer request bodies; "-" reads stdin
+    file: String,
+    #[arg(long, default_value_t = 8)]
+    concurrency: usize,
+    /// Send each line's request_id as-is instead of a fresh one. Ids the pool
+    /// has already seen come back as idempotent replays.
+    #[arg(long)]
+    keep_request_ids: bool,
+    #[command(flatten)]
+    overrides: Overrides,
+    /// Print the summary as JSON, for before/after comparisons
+    #[arg(long)]
+    json: bool,
+}
+
+impl Overrides {
+    fn apply(&self, request: &mut RequestLine) {
+        if let Some(strategy) = &self.strategy {
+            request.strategy = Some(strategy.clone());
+        }
+        if let Some(deadline_ms) = self.deadline_ms {
+            request.deadline_ms = Some(deadline_ms);
+        }
+        if let Some(tenant) = &self.tenant {
+            request.tenant_id = Some(tenant.clone());
+        }
+        if let Some(priority) = &self.priority {
+            request.priority = Some(priority.clone());
+        }
+    }
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let client = connect(&cli).await?;
+    match cli.command {
+        Command::Infer(args) => infer(client, args).await,
+        Command::Replay(args) => replay(client, args).await,
+    }
+}
+
+async fn connect(cli: &Cli) -> Result<Client> {
+    let credentials = match (&cli.api_key, &cli.secret) {
+        (Some(api_key), Some(secret)) => Some(Credentials { api_key: api_key.clone(), secret: secret.clone() }),
+        (None, None) => None,
+        _ => bail!("--api-key and --secret must be given together"),
+    };
+    let timeout = Duration::from_millis(cli.timeout_ms);
+    match cli.protocol {
+        Protocol::Http => {
+            let url = cli.url.as_deref().unwrap_or("http://localhost:7071");
+            Client::http(url, credentials, timeout).await
+        }
+        Protocol::Grpc => {
+            let url = cli.url.as_deref().unwrap_or("http://localhost:7070");
+            Client::grpc(url, credentials, timeout).await
+        }
+    }
+}
+
+async fn infer(client: Client, args: InferArgs) -> Result<()> {
+    let prompt = match args.prompt.as_deref() {
+        Some("-") => read_stdin()?,
+        Some(prompt) => prompt.to_string(),
+        None => String::new(),
+    };
+    let input = args.input.as_deref()
+        .map(serde_json::from_str)
+        .transpose()
+        .context("--input must be JSON")?;
+    let mut request = RequestLine {
+        request_id: Some(uuid::Uuid::new_v4().to_string()),
+        task: args.task,
+        prompt,
+        input,
+        max_tokens: args.max_tokens,
+        ..Default::default()
+    };
+    args.overrides.apply(&mut request);
+    
+    let reply = client.infer(&request)
+        .await
+        .map_err(|e| anyhow::anyhow!("{}: {}", e.kind, e.message))?;
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&reply)?);
+    } else {
+        println!("{}", reply.content);
+        eprintln!(
+            "winner={} strategy={} duration={}ms queue={}ms from_cache={}",
+            reply.winner_model, reply.strategy_used, reply.duration_ms, reply.queue_ms, reply.from_cache
+        );
+    }
+    Ok(())
+}
+
+async fn replay(client: Client, args: ReplayArgs) -> Result<()> {
+    let body = match args.file.as_str() {
+        "-" => read_stdin()?,
+        path => std::fs::read_to_string(path).with_context(|| format!("Cannot read {}", path))?,
+    };
+    let mut requests = Vec::new();
+    for (n, line) in body.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let mut request: RequestLine = serde_json::from_str(line)
+            .with_context(|| format!("line {}", n + 1))?;
+        if !args.keep_request_ids || request.request_id.is_none() {
+            request.request_id = Some(uuid::Uuid::new_v4().to_string());
+        }
+        args.overrides.apply(&mut request);
+        requests.push(request);
+    }
+    if requests.is_empty() {
+        bail!("No requests in {}", args.file);
+    }
+    
+    let start = Instant::now();
+    let outcomes = replay::run(client, requests, args.concurrency).await;
+    let summary = replay::summarize(&outcomes, start.elapsed());
+    if args.json {
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+    } else {
+        replay::print(&summary);
+    }
+    Ok(())
+}
+
+fn read_stdin() -> Result<String> {
+    let mut text = String::new();
+    std::io::stdin().read_to_string(&mut text).context("Cannot read stdin")?;
+    Ok(text)
+}
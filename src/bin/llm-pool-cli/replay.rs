@@ -0,0 +1,197 @@
+use crate::client::{CallError, Client, Reply, RequestLine};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Result of one replayed request.
+pub struct Outcome {
+    pub task: String,
+    pub requested_strategy: Option<String>,
+    pub latency: Duration,
+    pub result: Result<Reply, CallError>,
+}
+
+/// Sends every request with at most `concurrency` in flight.
+pub async fn run(client: Client, requests: Vec<RequestLine>, concurrency: usize) -> Vec<Outcome> {
+    let slots = Arc::new(Semaphore::new(concurrency.max(1)));
+    let mut running = tokio::task::JoinSet::new();
+    for request in requests {
+        let permit = slots.clone().acquire_owned().await.expect("semaphore is never closed");
+        let client = client.clone();
+        running.spawn(async move {
+            let start = Instant::now();
+            let result = client.infer(&request).await;
+            drop(permit);
+            Outcome {
+                task: request.task,
+                requested_strategy: request.strategy,
+                latency: start.elapsed(),
+                result,
+            }
+        });
+    }
+    
+    let mut outcomes = Vec::new();
+    while let Some(outcome) = running.join_next().await {
+        if let Ok(outcome) = outcome {
+            outcomes.push(outcome);
+        }
+    }
+    outcomes
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Summary {
+    #[serde(flatten)]
+    pub overall: GroupStats,
+    pub wall_ms: u64,
+    pub throughput_rps: f64,
+    /// Keyed by "task/strategy", with the strategy as requested
+    pub groups: BTreeMap<String, GroupStats>,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct GroupStats {
+    pub requests: usize,
+    pub succeeded: usize,
+    pub errors: BTreeMap<String, usize>,
+    pub cache_hit_rate: f64,
+    /// Client-side latency of successful requests, in milliseconds
+    pub latency_ms: Percentiles,
+    pub winners: BTreeMap<String, usize>,
+    /// Strategy each answer reports, e.g. CACHE for cache hits
+    pub strategies_used: BTreeMap<String, usize>,
+    #[serde(skip)]
+    latencies: Vec<f64>,
+    #[serde(skip)]
+    cache_hits: usize,
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct Percentiles {
+    pub p50: f64,
+    pub p90: f64,
+    pub p95: f64,
+    pub p99: f64,
+    pub max: f64,
+    pub mean: f64,
+}
+
+impl GroupStats {
+    fn record(&mut self, outcome: &Outcome) {
+        self.requests += 1;
+        match &outcome.result {
+            Ok(reply) => {
+                self.succeeded += 1;
+                self.latencies.push(outcome.latency.as_secs_f64() * 1000.0);
+                if reply.from_cache {
+                    self.cache_hits += 1;
+                }
+                *self.winners.entry(reply.winner_model.clone()).or_default() += 1;
+                *self.strategies_used.entry(reply.strategy_used.clone()).or_default() += 1;
+            }
+            Err(e) => *self.errors.entry(e.kind.clone()).or_default() += 1,
+        }
+    }
+    
+    fn finish(&mut self) {
+        self.cache_hit_rate = match self.succeeded {
+            0 => 0.0,
+            n => self.cache_hits as f64 / n as f64,
+        };
+        self.latency_ms = Percentiles::of(&mut self.latencies);
+    }
+}
+
+impl Percentiles {
+    /// Nearest-rank percentiles.
+    fn of(samples: &mut [f64]) -> Self {
+        if samples.is_empty() {
+            return Self::default();
+        }
+        samples.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let at = |p: f64| {
+            let rank = ((p / 100.0) * samples.len() as f64).ceil() as usize;
+            samples[rank.clamp(1, samples.len()) - 1]
+        };
+        Self {
+            p50: at(50.0),
+            p90: at(90.0),
+            p95: at(95.0),
+            p99: at(99.0),
+            max: samples[samples.len() - 1],
+            mean: samples.iter().sum::<f64>() / samples.len() as f64,
+        }
+    }
+}
+
+pub fn summarize(outcomes: &[Outcome], wall: Duration) -> Summary {
+    let mut summary = Summary {
+        wall_ms: wall.as_millis() as u64,
+        throughput_rps: outcomes.len() as f64 / wall.as_secs_f64().max(0.001),
+        ..Default::default()
+    };
+    for outcome in outcomes {
+        // Cache hits come back as strategy CACHE, so group by what was asked
+        // for to keep them with the fresh answers they stand in for
+        let strategy = outcome.requested_strategy.as_deref()
+            .map(str::to_ascii_uppercase)
+            .unwrap_or_else(|| "default".to_string());
+        summary.overall.record(outcome);
+        summary.groups
+            .entry(format!("{}/{}", outcome.task, strategy))
+            .or_default()
+            .record(outcome);
+    }
+    summary.overall.finish();
+    summary.groups.values_mut().for_each(GroupStats::finish);
+    summary
+}
+
+pub fn print(summary: &Summary) {
+    println!(
+        "{} requests in {:.1}s ({:.1} req/s)",
+        summary.overall.requests,
+        summary.wall_ms as f64 / 1000.0,
+        summary.throughput_rps
+    );
+    print_group("all", &summary.overall);
+    for (name, group) in &summary.groups {
+        print_group(name, group);
+    }
+}
+
+fn print_group(name: &str, stats: &GroupStats) {
+    let l = &stats.latency_ms;
+    println!();
+    println!("== {} ==", name);
+    println!(
+        "  ok {}/{}   cache hits {:.1}%",
+        stats.succeeded,
+        stats.requests,
+        stats.cache_hit_rate * 100.0
+    );
+    println!(
+        "  latency ms  p50 {:.0}  p90 {:.0}  p95 {:.0}  p99 {:.0}  max {:.0}  mean {:.0}",
+        l.p50, l.p90, l.p95, l.p99, l.max, l.mean
+    );
+    let used: Vec<String> = stats.strategies_used.iter()
+        .map(|(strategy, count)| format!("{} {}", strategy, count))
+        .collect();
+    if !used.is_empty() {
+        println!("  served by   {}", used.join(", "));
+    }
+    for (kind, count) in &stats.errors {
+        println!("  error {:<24} {}", kind, count);
+    }
+    for (model, count) in &stats.winners {
+        println!(
+            "  winner {:<23} {} ({:.1}%)",
+            model,
+            count,
+            *count as f64 * 100.0 / stats.succeeded.max(1) as f64
+        );
+    }
+}
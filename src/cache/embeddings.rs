@@ -0,0 +1,37 @@
+use moka::future::Cache as MokaCache;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Vectors by provider and input text. A model always returns the same
+/// vector for the same input, so entries can outlive generated answers.
+pub struct EmbeddingCache {
+    store: MokaCache<String, Arc<Vec<f32>>>,
+}
+
+impl EmbeddingCache {
+    pub fn new(ttl_seconds: u64, max_capacity: u64) -> Self {
+        let store = MokaCache::builder()
+            .time_to_live(Duration::from_secs(ttl_seconds))
+            .max_capacity(max_capacity)
+            .build();
+        
+        Self { store }
+    }
+    
+    pub async fn get(&self, provider: &str, input: &str) -> Option<Arc<Vec<f32>>> {
+        self.store.get(&Self::key(provider, input)).await
+    }
+    
+    pub async fn insert(&self, provider: &str, input: &str, vector: Vec<f32>) {
+        self.store.insert(Self::key(provider, input), Arc::new(vector)).await;
+    }
+    
+    fn key(provider: &str, input: &str) -> String {
+        use sha2::{Sha256, Digest};
+        let mut hasher = Sha256::new();
+        hasher.update(provider.as_bytes());
+        hasher.update([0]);
+        hasher.update(input.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
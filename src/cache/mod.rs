@@ -0,0 +1,279 @@
+mod embeddings;
+mod redis;
+mod semantic;
+
+pub use self::embeddings::EmbeddingCache;
+pub use self::semantic::SemanticCache;
+
+use self::redis::RedisStore;
+use crate::config::CacheConfig;
+use moka::future::Cache as MokaCache;
+use moka::Expiry;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct CachedResponse {
+    pub content: String,
+    pub model: String,
+    /// Unix seconds when the answer was produced
+    #[serde(default)]
+    pub stored_at: u64,
+    #[serde(default)]
+    pub task: String,
+    /// Tenants whose requests stored this answer
+    #[serde(default)]
+    pub tenants: Vec<String>,
+    /// Freshness window for this answer; the cache-wide TTL when unset
+    #[serde(default)]
+    pub ttl_seconds: Option<u64>,
+}
+
+impl CachedResponse {
+    pub fn new(task: &str, tenant_id: &str, content: &str, model: &str) -> Self {
+        Self {
+            content: content.to_string(),
+            model: model.to_string(),
+            stored_at: now_secs(),
+            task: task.to_string(),
+            tenants: vec![tenant_id.to_string()],
+            ttl_seconds: None,
+        }
+    }
+    
+    pub fn age_secs(&self) -> u64 {
+        now_secs().saturating_sub(self.stored_at)
+    }
+}
+
+/// Selects entries for admin invalidation.
+#[derive(Debug, Clone)]
+pub enum CacheFilter {
+    All,
+    Key(String),
+    Task(String),
+    /// Entries stored by the tenant, including ones other tenants stored too
+    Tenant(String),
+}
+
+impl CacheFilter {
+    pub fn matches(&self, key: &str, entry: &CachedResponse) -> bool {
+        match self {
+            CacheFilter::All => true,
+            CacheFilter::Key(k) => k == key,
+            CacheFilter::Task(task) => &entry.task == task,
+            CacheFilter::Tenant(tenant) => entry.tenants.contains(tenant),
+        }
+    }
+}
+
+#[derive(Debug, Default, Serialize)]
+pub struct TaskStats {
+    pub entries: u64,
+    pub bytes: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_ratio: f64,
+}
+
+/// Keeps each entry for its own TTL plus the stale-if-error grace period.
+struct EntryExpiry {
+    ttl_seconds: u64,
+    stale_seconds: u64,
+}
+
+impl Expiry<String, CachedResponse> for EntryExpiry {
+    fn expire_after_create(&self, _key: &String, value: &CachedResponse, _created_at: Instant) -> Option<Duration> {
+        let ttl = value.ttl_seconds.unwrap_or(self.ttl_seconds);
+        Some(Duration::from_secs(ttl + self.stale_seconds))
+    }
+}
+
+/// Entries live for their TTL plus `stale_seconds`. Only the first part
+/// is served normally; the tail is kept for `get_stale` when every provider
+/// is failing.
+pub struct Cache {
+    store: MokaCache<String, CachedResponse>,
+    remote: Option<RedisStore>,
+    ttl_seconds: u64,
+    stale_seconds: u64,
+    /// (hits, misses) per task since startup
+    lookups: Mutex<HashMap<String, (u64, u64)>>,
+}
+
+impl Cache {
+    pub fn new(ttl_seconds: u64, stale_seconds: u64, max_capacity: u64) -> Self {
+        let store = MokaCache::builder()
+            .expire_after(EntryExpiry { ttl_seconds, stale_seconds })
+            .max_capacity(max_capacity)
+            .build();
+        
+        Self {
+            store,
+            remote: None,
+            ttl_seconds,
+            stale_seconds,
+            lookups: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Builds the cache for the configured driver. The in-process tier is
+    /// always present; `driver = "redis"` adds a shared tier behind it so
+    /// several pool replicas see each other's answers.
+    pub fn from_config(config: &CacheConfig, max_capacity: u64) -> Self {
+        let mut cache = Self::new(config.ttl_seconds, config.stale_if_error_seconds, max_capacity);
+        
+        match config.driver.as_str() {
+            "memory" => {}
+            "redis" => match RedisStore::new(config) {
+                Ok(remote) => {
+                    info!("🗄️  Redis cache tier enabled: {}", config.redis_url);
+                    cache.remote = Some(remote);
+                }
+                Err(e) => {
+                    warn!("⚠️  Redis cache disabled, using memory only: {}", e);
+                }
+            },
+            other => {
+                warn!("Unknown cache driver: {}, using memory only", other);
+            }
+        }
+        
+        cache
+    }
+    
+    pub async fn get(&self, task: &str, key: &str) -> Option<CachedResponse> {
+        let hit = self.lookup(key).await
+            .filter(|hit| hit.age_secs() < self.ttl_of(hit));
+        
+        let mut lookups = self.lookups.lock().unwrap();
+        let (hits, misses) = lookups.entry(task.to_string()).or_default();
+        if hit.is_some() {
+            *hits += 1;
+        } else {
+            *misses += 1;
+        }
+        hit
+    }
+    
+    /// Like `get`, but also returns entries past their TTL that are still
+    /// within the stale-if-error grace period.
+    pub async fn get_stale(&self, key: &str) -> Option<CachedResponse> {
+        if self.stale_seconds == 0 {
+            return None;
+        }
+        self.lookup(key).await
+            .filter(|hit| hit.age_secs() < self.ttl_of(hit) + self.stale_seconds)
+    }
+    
+    async fn lookup(&self, key: &str) -> Option<CachedResponse> {
+        let local = self.store.get(key).await;
+        if local.as_ref().is_some_and(|hit| hit.age_secs() < self.ttl_of(hit)) {
+            return local;
+        }
+        
+        // Fall through to the shared tier, where another replica may have
+        // stored a fresher answer; a stale local copy is only the fallback
+        let Some(remote) = &self.remote else {
+            return local;
+        };
+        match remote.get(key).await {
+            Some(hit) if local.as_ref().is_none_or(|stale| hit.stored_at >= stale.stored_at) => {
+                self.store.insert(key.to_string(), hit.clone()).await;
+                Some(hit)
+            }
+            _ => local,
+        }
+    }
+    
+    /// Stores an answer. Tenants that stored the entry it replaces stay on
+    /// it, so invalidating any of them still finds it.
+    pub async fn set(&self, key: &str, mut value: CachedResponse) {
+        let previous = match self.store.get(key).await {
+            Some(entry) => Some(entry),
+            None => match &self.remote {
+                Some(remote) => remote.get(key).await,
+                None => None,
+            },
+        };
+        for tenant in previous.into_iter().flat_map(|entry| entry.tenants) {
+            if !value.tenants.contains(&tenant) {
+                value.tenants.push(tenant);
+            }
+        }
+        
+        if let Some(remote) = &self.remote {
+            remote.set(key, &value, self.ttl_of(&value) + self.stale_seconds).await;
+        }
+        self.store.insert(key.to_string(), value).await;
+    }
+    
+    fn ttl_of(&self, entry: &CachedResponse) -> u64 {
+        entry.ttl_seconds.unwrap_or(self.ttl_seconds)
+    }
+    
+    /// Per-task entry counts and sizes for the local tier, plus lookup
+    /// counters since startup.
+    pub fn stats(&self) -> BTreeMap<String, TaskStats> {
+        let mut stats: BTreeMap<String, TaskStats> = BTreeMap::new();
+        
+        for (key, entry) in self.store.iter() {
+            let task = stats.entry(entry.task.clone()).or_default();
+            task.entries += 1;
+            task.bytes += (key.len() + entry.content.len() + entry.model.len()) as u64;
+        }
+        
+        for (task, (hits, misses)) in self.lookups.lock().unwrap().iter() {
+            let task = stats.entry(task.clone()).or_default();
+            task.hits = *hits;
+            task.misses = *misses;
+            if hits + misses > 0 {
+                task.hit_ratio = *hits as f64 / (hits + misses) as f64;
+            }
+        }
+        
+        stats
+    }
+    
+    /// Drops matching entries from both tiers and returns how many were
+    /// removed from each (local, shared).
+    pub async fn invalidate(&self, filter: &CacheFilter) -> (u64, u64) {
+        let keys: Vec<String> = self.store.iter()
+            .filter(|(key, entry)| filter.matches(key, entry))
+            .map(|(key, _)| key.as_ref().clone())
+            .collect();
+        
+        for key in &keys {
+            self.store.invalidate(key).await;
+        }
+        
+        let shared = match &self.remote {
+            Some(remote) => remote.invalidate(filter).await,
+            None => 0,
+        };
+        
+        (keys.len() as u64, shared)
+    }
+    
+    /// `variant` covers any other request settings that change the answer;
+    /// an empty variant yields the same key as before it existed.
+    pub fn make_key(&self, task: &str, prompt: &str, max_tokens: i32, variant: &str) -> String {
+        use sha2::{Sha256, Digest};
+        let mut hasher = Sha256::new();
+        hasher.update(task.as_bytes());
+        hasher.update(prompt.as_bytes());
+        hasher.update(max_tokens.to_string().as_bytes());
+        hasher.update(variant.as_bytes());
+        format!("{:x}", hasher.finalize())
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
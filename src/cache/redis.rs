@@ -0,0 +1,448 @@
+use super::{CacheFilter, CachedResponse};
+use crate::config::CacheConfig;
+use crate::errors::{LLMPoolError, Result};
+use redis::aio::ConnectionManager;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex as StdMutex;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{debug, warn};
+
+/// How long to skip the shared tier after it fails, so an unreachable
+/// server costs one timeout instead of one per request.
+const RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// Shared cache tier speaking RESP to Redis or any compatible server
+/// (Valkey, KeyDB, Dragonfly, ...).
+///
+/// Connections are opened lazily into a fixed set of slots and handed out
+/// round-robin. Every failure is swallowed: the caller just sees a miss and
+/// keeps serving from memory.
+pub struct RedisStore {
+    client: redis::Client,
+    slots: Vec<Mutex<Option<ConnectionManager>>>,
+    next: AtomicUsize,
+    prefix: String,
+    timeout: Duration,
+    down_until: StdMutex<Option<Instant>>,
+}
+
+impl RedisStore {
+    pub fn new(config: &CacheConfig) -> Result<Self> {
+        let client = redis::Client::open(config.redis_url.as_str())
+            .map_err(|e| LLMPoolError::CacheError(format!("Invalid redis_url: {}", e)))?;
+        
+        let slots = (0..config.redis_pool_size.max(1))
+            .map(|_| Mutex::new(None))
+            .collect();
+        
+        Ok(Self {
+            client,
+            slots,
+            next: AtomicUsize::new(0),
+            prefix: config.redis_key_prefix.clone(),
+            timeout: Duration::from_millis(config.redis_timeout_ms),
+            down_until: StdMutex::new(None),
+        })
+    }
+    
+    pub async fn get(&self, key: &str) -> Option<CachedResponse> {
+        let mut conn = self.connection().await?;
+        let mut cmd = redis::cmd("GET");
+        cmd.arg(self.key(key));
+        
+        let raw: Option<String> = match tokio::time::timeout(self.timeout, cmd.query_async(&mut conn)).await {
+            Ok(Ok(raw)) => raw,
+            Ok(Err(e)) => return self.fail(format!("GET failed: {}", e)),
+            Err(_) => return self.fail("GET timed out".to_string()),
+        };
+        
+        match serde_json::from_str(&raw?) {
+            Ok(value) => Some(value),
+            Err(e) => {
+                debug!("Ignoring undecodable cache entry: {}", e);
+                None
+            }
+        }
+    }
+    
+    pub async fn set(&self, key: &str, value: &CachedResponse, ttl_seconds: u64) {
+        let Some(mut conn) = self.connection().await else {
+            return;
+        };
+        let Ok(payload) = serde_json::to_string(value) else {
+            return;
+        };
+        
+        // SET ... EX lets the server expire entries on the same TTL as memory
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(key)).arg(payload).arg("EX").arg(ttl_seconds.max(1));
+        
+        match tokio::time::timeout(self.timeout, cmd.query_async::<()>(&mut conn)).await {
+            Ok(Ok(())) => {}
+            Ok(Err(e)) => {
+                self.fail::<()>(format!("SET failed: {}", e));
+            }
+            Err(_) => {
+                self.fail::<()>("SET timed out".to_string());
+            }
+        }
+    }
+    
+    /// Deletes matching entries and returns how many were removed. Filters
+    /// other than a single key walk the prefix with SCAN, so this is meant
+    /// for admin use, not the request path.
+    pub async fn invalidate(&self, filter: &CacheFilter) -> u64 {
+        let Some(mut conn) = self.connection().await else {
+            return 0;
+        };
+        
+        if let CacheFilter::Key(key) = filter {
+            return self.delete(&mut conn, &[self.key(key)]).await;
+        }
+        
+        let pattern = format!("{}*", self.prefix);
+        let mut cursor = 0u64;
+        let mut removed = 0;
+        loop {
+            let mut cmd = redis::cmd("SCAN");
+            cmd.arg(cursor).arg("MATCH").arg(&pattern).arg("COUNT").arg(500);
+            let (next, keys): (u64, Vec<String>) = match tokio::time::timeout(self.timeout, cmd.query_async(&mut conn)).await {
+                Ok(Ok(page)) => page,
+                Ok(Err(e)) => {
+                    self.fail::<()>(format!("SCAN failed: {}", e));
+                    return removed;
+                }
+                Err(_) => {
+                    self.fail::<()>("SCAN timed out".to_string());
+                    return removed;
+                }
+            };
+            
+            let doomed = match filter {
+                CacheFilter::All => keys,
+                _ => self.matching(&mut conn, keys, filter).await,
+            };
+            removed += self.delete(&mut conn, &doomed).await;
+            
+            if next == 0 {
+                return removed;
+            }
+            cursor = next;
+        }
+    }
+    
+    async fn matching(&self, conn: &mut ConnectionManager, keys: Vec<String>, filter: &CacheFilter) -> Vec<String> {
+        if keys.is_empty() {
+            return keys;
+        }
+        
+        let mut cmd = redis::cmd("MGET");
+        cmd.arg(&keys);
+        let values: Vec<Option<String>> = match tokio::time::timeout(self.timeout, cmd.query_async(conn)).await {
+            Ok(Ok(values)) => values,
+            _ => return Vec::new(),
+        };
+        
+        keys.into_iter()
+            .zip(values)
+            .filter_map(|(key, raw)| {
+                let entry: CachedResponse = serde_json::from_str(&raw?).ok()?;
+                let short = key.strip_prefix(&self.prefix).unwrap_or(&key);
+                filter.matches(short, &entry).then_some(key)
+            })
+            .collect()
+    }
+    
+    async fn delete(&self, conn: &mut ConnectionManager, keys: &[String]) -> u64 {
+        if keys.is_empty() {
+            return 0;
+        }
+        
+        let mut cmd = redis::cmd("DEL");
+        cmd.arg(keys);
+        match tokio::time::timeout(self.timeout, cmd.query_async(conn)).await {
+            Ok(Ok(n)) => n,
+            Ok(Err(e)) => {
+                self.fail::<()>(format!("DEL failed: {}", e));
+                0
+            }
+            Err(_) => {
+                self.fail::<()>("DEL timed out".to_string());
+                0
+            }
+        }
+    }
+    
+    fn key(&self, key: &str) -> String {
+        format!("{}{}", self.prefix, key)
+    }
+    
+    /// Returns a connection from the next slot, connecting it if needed.
+    async fn connection(&self) -> Option<ConnectionManager> {
+        if let Some(until) = *self.down_until.lock().unwrap() {
+            if Instant::now() < until {
+                return None;
+            }
+        }
+        
+        let idx = self.next.fetch_add(1, Ordering::Relaxed) % self.slots.len();
+        let mut slot = self.slots[idx].lock().await;
+        if let Some(conn) = slot.as_ref() {
+            return Some(conn.clone());
+        }
+        
+        match tokio::time::timeout(self.timeout, self.client.get_connection_manager()).await {
+            Ok(Ok(conn)) => {
+                *self.down_until.lock().unwrap() = None;
+                *slot = Some(conn.clone());
+                Some(conn)
+            }
+            Ok(Err(e)) => self.fail(format!("connect failed: {}", e)),
+            Err(_) => self.fail("connect timed out".to_string()),
+        }
+    }
+    
+    fn fail<T>(&self, reason: String) -> Option<T> {
+        let mut down_until = self.down_until.lock().unwrap();
+        if down_until.is_none_or(|until| Instant::now() >= until) {
+            warn!("⚠️  Redis cache unreachable ({}), serving from memory for {:?}", reason, RETRY_AFTER);
+        }
+        *down_until = Some(Instant::now() + RETRY_AFTER);
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::task::JoinHandle;
+    
+    /// Key → (value, expiry)
+    type Store = Arc<StdMutex<HashMap<String, (String, Option<Instant>)>>>;
+    
+    /// Just enough of a RESP server for the store: GET, SET ... EX, MGET,
+    /// SCAN (one page) and DEL, with expiry.
+    struct FakeRedis {
+        url: String,
+        data: Store,
+        /// TTL of each SET, in arrival order
+        ttls: Arc<StdMutex<Vec<u64>>>,
+        tasks: Arc<StdMutex<Vec<JoinHandle<()>>>>,
+    }
+    
+    impl FakeRedis {
+        async fn start() -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("redis://{}", listener.local_addr().unwrap());
+            let data = Arc::new(StdMutex::new(HashMap::new()));
+            let ttls = Arc::new(StdMutex::new(Vec::new()));
+            let tasks = Arc::new(StdMutex::new(Vec::new()));
+            
+            let (d, t, handles) = (data.clone(), ttls.clone(), tasks.clone());
+            let accept = tokio::spawn(async move {
+                while let Ok((socket, _)) = listener.accept().await {
+                    let conn = tokio::spawn(serve(socket, d.clone(), t.clone()));
+                    handles.lock().unwrap().push(conn);
+                }
+            });
+            tasks.lock().unwrap().push(accept);
+            Self { url, data, ttls, tasks }
+        }
+        
+        /// Stops listening and drops every open connection.
+        fn shutdown(&self) {
+            for task in self.tasks.lock().unwrap().drain(..) {
+                task.abort();
+            }
+        }
+    }
+    
+    async fn serve(
+        socket: TcpStream,
+        data: Store,
+        ttls: Arc<StdMutex<Vec<u64>>>,
+    ) {
+        let (read, mut write) = socket.into_split();
+        let mut read = BufReader::new(read);
+        while let Some(args) = read_command(&mut read).await {
+            let reply = execute(&args, &data, &ttls);
+            if write.write_all(reply.as_bytes()).await.is_err() {
+                return;
+            }
+        }
+    }
+    
+    async fn read_command(read: &mut BufReader<tokio::net::tcp::OwnedReadHalf>) -> Option<Vec<String>> {
+        let mut line = String::new();
+        read.read_line(&mut line).await.ok().filter(|n| *n > 0)?;
+        let count: usize = line.trim().strip_prefix('*')?.parse().ok()?;
+        let mut args = Vec::with_capacity(count);
+        for _ in 0..count {
+            line.clear();
+            read.read_line(&mut line).await.ok()?;
+            let len: usize = line.trim().strip_prefix('$')?.parse().ok()?;
+            let mut arg = vec![0; len + 2];
+            read.read_exact(&mut arg).await.ok()?;
+            arg.truncate(len);
+            args.push(String::from_utf8(arg).ok()?);
+        }
+        Some(args)
+    }
+    
+    fn bulk(value: Option<&str>) -> String {
+        match value {
+            Some(v) => format!("${}\r\n{}\r\n", v.len(), v),
+            None => "$-1\r\n".to_string(),
+        }
+    }
+    
+    fn execute(
+        args: &[String],
+        data: &Store,
+        ttls: &StdMutex<Vec<u64>>,
+    ) -> String {
+        let mut data = data.lock().unwrap();
+        data.retain(|_, (_, expires)| expires.is_none_or(|at| Instant::now() < at));
+        match args[0].to_ascii_uppercase().as_str() {
+            "GET" => bulk(data.get(&args[1]).map(|(v, _)| v.as_str())),
+            "SET" => {
+                let ttl = match args.get(3).map(|a| a.to_ascii_uppercase()) {
+                    Some(ex) if ex == "EX" => args[4].parse::<u64>().ok(),
+                    _ => None,
+                };
+                ttls.lock().unwrap().push(ttl.unwrap_or(0));
+                let expires = ttl.map(|secs| Instant::now() + Duration::from_secs(secs));
+                data.insert(args[1].clone(), (args[2].clone(), expires));
+                "+OK\r\n".to_string()
+            }
+            "MGET" => {
+                let values: String = args[1..].iter()
+                    .map(|key| bulk(data.get(key).map(|(v, _)| v.as_str())))
+                    .collect();
+                format!("*{}\r\n{}", args.len() - 1, values)
+            }
+            "SCAN" => {
+                let prefix = args.iter()
+                    .position(|a| a.eq_ignore_ascii_case("MATCH"))
+                    .map(|i| args[i + 1].trim_end_matches('*').to_string())
+                    .unwrap_or_default();
+                let keys: Vec<&String> = data.keys().filter(|k| k.starts_with(&prefix)).collect();
+                let page: String = keys.iter().map(|k| bulk(Some(k))).collect();
+                format!("*2\r\n{}*{}\r\n{}", bulk(Some("0")), keys.len(), page)
+            }
+            "DEL" => {
+                let removed = args[1..].iter().filter(|key| data.remove(*key).is_some()).count();
+                format!(":{}\r\n", removed)
+            }
+            _ => "-ERR unknown command\r\n".to_string(),
+        }
+    }
+    
+    fn config(url: &str) -> CacheConfig {
+        let mut config: CacheConfig = toml::from_str("").unwrap();
+        config.driver = "redis".to_string();
+        config.redis_url = url.to_string();
+        config.redis_timeout_ms = 500;
+        config.ttl_seconds = 60;
+        config
+    }
+    
+    fn answer(content: &str) -> CachedResponse {
+        CachedResponse::new("expand_queries", "acme", content, "phi3:mini")
+    }
+    
+    #[tokio::test]
+    async fn shares_answers_with_their_ttl() {
+        let server = FakeRedis::start().await;
+        let writer = Cache::from_config(&config(&server.url), 100);
+        writer.set("k1", answer("first")).await;
+        writer.set("k2", CachedResponse { ttl_seconds: Some(1), ..answer("short") }).await;
+        assert_eq!(*server.ttls.lock().unwrap(), vec![60, 1]);
+        assert!(server.data.lock().unwrap().contains_key("llmpool:k1"));
+        
+        // A second replica has nothing in memory and reads the shared tier
+        let reader = Cache::from_config(&config(&server.url), 100);
+        let hit = reader.get("expand_queries", "k1").await.expect("shared hit");
+        assert_eq!(hit.content, "first");
+        
+        // The server expires entries on the TTL it was given
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(reader.remote.as_ref().unwrap().get("k2").await.is_none());
+        
+        assert_eq!(reader.invalidate(&CacheFilter::All).await.1, 1);
+        assert!(server.data.lock().unwrap().is_empty());
+        server.shutdown();
+    }
+    
+    #[tokio::test]
+    async fn prefers_a_fresh_shared_answer_over_a_stale_local_copy() {
+        let server = FakeRedis::start().await;
+        let mut config = config(&server.url);
+        config.stale_if_error_seconds = 60;
+        let cache = Cache::from_config(&config, 100);
+        cache.set("k1", CachedResponse { ttl_seconds: Some(1), ..answer("old") }).await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        
+        // Another replica answers the same request again
+        let other = Cache::from_config(&config, 100);
+        other.set("k1", answer("new")).await;
+        assert_eq!(cache.get("expand_queries", "k1").await.unwrap().content, "new");
+        
+        // With nothing newer shared, the stale copy still backs failures
+        cache.set("k2", CachedResponse { ttl_seconds: Some(1), ..answer("stale") }).await;
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        server.data.lock().unwrap().clear();
+        assert!(cache.get("expand_queries", "k2").await.is_none());
+        assert_eq!(cache.get_stale("k2").await.unwrap().content, "stale");
+        server.shutdown();
+    }
+    
+    #[tokio::test]
+    async fn invalidates_a_tenants_entries_in_both_tiers() {
+        let server = FakeRedis::start().await;
+        let writer = Cache::from_config(&config(&server.url), 100);
+        writer.set("k1", answer("acme's")).await;
+        writer.set("k2", CachedResponse { tenants: vec!["globex".to_string()], ..answer("globex's") }).await;
+        
+        // Another replica stores the same answer for globex; acme stays on it
+        let other = Cache::from_config(&config(&server.url), 100);
+        other.set("k1", CachedResponse { tenants: vec!["globex".to_string()], ..answer("acme's") }).await;
+        let shared = other.remote.as_ref().unwrap().get("k1").await.unwrap();
+        assert_eq!(shared.tenants, ["globex", "acme"]);
+        
+        assert_eq!(writer.invalidate(&CacheFilter::Tenant("acme".to_string())).await, (1, 1));
+        assert!(writer.get("expand_queries", "k1").await.is_none());
+        assert_eq!(writer.get("expand_queries", "k2").await.unwrap().content, "globex's");
+        let keys: Vec<String> = server.data.lock().unwrap().keys().cloned().collect();
+        assert_eq!(keys, ["llmpool:k2"]);
+        server.shutdown();
+    }
+    
+    #[tokio::test]
+    async fn serves_from_memory_when_the_server_goes_away() {
+        let server = FakeRedis::start().await;
+        let cache = Cache::from_config(&config(&server.url), 100);
+        cache.set("k1", answer("kept")).await;
+        server.shutdown();
+        
+        // The failed lookup marks the shared tier down instead of erroring
+        let remote = cache.remote.as_ref().unwrap();
+        assert!(remote.get("missing").await.is_none());
+        let until = remote.down_until.lock().unwrap().expect("tier marked down");
+        assert!(until > Instant::now() + RETRY_AFTER - Duration::from_secs(1));
+        
+        // While down, calls skip the server and answers come from memory
+        let start = Instant::now();
+        assert!(remote.get("missing").await.is_none());
+        assert!(start.elapsed() < Duration::from_millis(50));
+        assert_eq!(cache.get("expand_queries", "k1").await.unwrap().content, "kept");
+        cache.set("k2", answer("local")).await;
+        assert_eq!(cache.get("expand_queries", "k2").await.unwrap().content, "local");
+    }
+}
@@ -0,0 +1,239 @@
+use super::{CacheFilter, CachedResponse};
+use crate::config::SemanticCacheConfig;
+use crate::providers::{InferParams, Provider, ProviderPool};
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+struct Entry {
+    /// Exact-cache key of the prompt that produced the answer
+    key: String,
+    vector: Vec<f32>,
+    /// Only requests with the same max_tokens and settings may reuse it
+    scope: String,
+    response: CachedResponse,
+    inserted_at: Instant,
+}
+
+/// Nearest-neighbour tier behind the exact-hash cache.
+///
+/// Prompts are embedded through a provider and kept per task in a flat,
+/// in-process index. Lookups are a linear scan, which is fine at the few
+/// thousand entries per task this tier is meant for.
+pub struct SemanticCache {
+    embedder: Arc<dyn Provider>,
+    thresholds: HashMap<String, f32>,
+    max_entries_per_task: usize,
+    embed_timeout: Duration,
+    ttl: Duration,
+    index: RwLock<HashMap<String, VecDeque<Entry>>>,
+}
+
+impl SemanticCache {
+    /// Returns `None` when the tier is disabled or its embedder is missing.
+    pub fn from_config(
+        config: &SemanticCacheConfig,
+        providers: &ProviderPool,
+        ttl_seconds: u64,
+    ) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        
+        let Some(name) = config.embed_provider.as_deref() else {
+            warn!("Semantic cache enabled without embed_provider, skipping");
+            return None;
+        };
+        let Some(embedder) = providers.get(name) else {
+            warn!("Semantic cache embed_provider not found: {}", name);
+            return None;
+        };
+        
+        info!("🧭 Semantic cache enabled via {} for tasks: {:?}",
+            name, config.thresholds.keys().collect::<Vec<_>>());
+        
+        Some(Self {
+            embedder,
+            thresholds: config.thresholds.clone(),
+            max_entries_per_task: config.max_entries_per_task.max(1),
+            embed_timeout: Duration::from_millis(config.embed_timeout_ms),
+            ttl: Duration::from_secs(ttl_seconds),
+            index: RwLock::new(HashMap::new()),
+        })
+    }
+    
+    pub fn covers(&self, task: &str) -> bool {
+        self.thresholds.contains_key(task)
+    }
+    
+    /// Embeds a prompt, giving up after `embed_timeout_ms` so a slow
+    /// embedder costs the request at most that much of its deadline.
+    /// `params` carries the request's tenant and priority into the
+    /// embedder's queue.
+    pub async fn embed(&self, prompt: &str, params: &InferParams) -> Option<Vec<f32>> {
+        let inputs = [prompt.to_string()];
+        let params = InferParams { deadline_ms: self.embed_timeout.as_millis() as i32, ..params.clone() };
+        match tokio::time::timeout(self.embed_timeout, self.embedder.embed(&inputs, &params)).await {
+            Ok(Ok(mut vectors)) => vectors.pop().map(normalize),
+            Ok(Err(e)) => {
+                debug!("Semantic cache embed failed: {}", e);
+                None
+            }
+            Err(_) => {
+                debug!("Semantic cache embed timed out");
+                None
+            }
+        }
+    }
+    
+    /// Returns the closest live entry for the task at or above its
+    /// threshold, together with the cosine similarity.
+    pub fn lookup(&self, task: &str, scope: &str, vector: &[f32]) -> Option<(CachedResponse, f32)> {
+        let threshold = *self.thresholds.get(task)?;
+        let index = self.index.read().unwrap();
+        
+        index
+            .get(task)?
+            .iter()
+            .filter(|e| e.scope == scope && self.is_live(e))
+            .filter(|e| e.vector.len() == vector.len())
+            .map(|e| (e, dot(&e.vector, vector)))
+            .filter(|(_, score)| *score >= threshold)
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(e, score)| (e.response.clone(), score))
+    }
+    
+    pub fn insert(&self, key: String, scope: String, vector: Vec<f32>, response: CachedResponse) {
+        let task = response.task.clone();
+        let mut index = self.index.write().unwrap();
+        let entries = index.entry(task).or_default();
+        
+        entries.retain(|e| self.is_live(e));
+        while entries.len() >= self.max_entries_per_task {
+            entries.pop_front();
+        }
+        
+        entries.push_back(Entry {
+            key,
+            vector,
+            scope,
+            response,
+            inserted_at: Instant::now(),
+        });
+    }
+    
+    fn is_live(&self, entry: &Entry) -> bool {
+        let ttl = entry.response.ttl_seconds.map_or(self.ttl, Duration::from_secs);
+        entry.inserted_at.elapsed() < ttl
+    }
+    
+    pub fn invalidate(&self, filter: &CacheFilter) -> u64 {
+        let mut index = self.index.write().unwrap();
+        let mut removed = 0;
+        for entries in index.values_mut() {
+            let before = entries.len();
+            entries.retain(|e| !filter.matches(&e.key, &e.response));
+            removed += (before - entries.len()) as u64;
+        }
+        removed
+    }
+}
+
+/// Scales to unit length so similarity is a plain dot product.
+fn normalize(mut v: Vec<f32>) -> Vec<f32> {
+    let norm = v.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        v.iter_mut().for_each(|x| *x /= norm);
+    }
+    v
+}
+
+fn dot(a: &[f32], b: &[f32]) -> f32 {
+    a.iter().zip(b).map(|(x, y)| x * y).sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::Result;
+    use crate::providers::ProviderResponse;
+    use async_trait::async_trait;
+    
+    /// Embeds each prompt to a fixed vector after an optional delay.
+    struct Embedder {
+        vectors: HashMap<String, Vec<f32>>,
+        delay: Duration,
+    }
+    
+    #[async_trait]
+    impl Provider for Embedder {
+        fn name(&self) -> &str { "embedder" }
+        fn supports(&self, _task: &str) -> bool { true }
+        async fn infer(&self, _prompt: &str, _params: &InferParams) -> Result<ProviderResponse> {
+            unreachable!("the semantic tier only embeds")
+        }
+        async fn health(&self) -> bool { true }
+        async fn embed(&self, inputs: &[String], _params: &InferParams) -> Result<Vec<Vec<f32>>> {
+            tokio::time::sleep(self.delay).await;
+            Ok(inputs.iter().map(|i| self.vectors[i].clone()).collect())
+        }
+    }
+    
+    fn embedder(delay: Duration) -> Arc<dyn Provider> {
+        let vectors = HashMap::from([
+            ("stored".to_string(), vec![1.0, 0.0]),
+            ("close".to_string(), vec![0.99, 0.1]),
+            ("far".to_string(), vec![0.6, 0.8]),
+        ]);
+        Arc::new(Embedder { vectors, delay })
+    }
+    
+    fn cache() -> SemanticCache {
+        SemanticCache {
+            embedder: embedder(Duration::ZERO),
+            thresholds: HashMap::from([("summarize".to_string(), 0.95)]),
+            max_entries_per_task: 10,
+            embed_timeout: Duration::from_millis(50),
+            ttl: Duration::from_secs(60),
+            index: RwLock::new(HashMap::new()),
+        }
+    }
+    
+    async fn store(cache: &SemanticCache) {
+        let vector = cache.embed("stored", &InferParams::default()).await.unwrap();
+        let response = CachedResponse::new("summarize", "acme", "answer", "model");
+        cache.insert("key".to_string(), "0:".to_string(), vector, response);
+    }
+    
+    #[tokio::test]
+    async fn a_prompt_above_the_threshold_reuses_the_answer() {
+        let cache = cache();
+        store(&cache).await;
+        
+        let vector = cache.embed("close", &InferParams::default()).await.unwrap();
+        let (response, similarity) = cache.lookup("summarize", "0:", &vector).unwrap();
+        assert_eq!(response.content, "answer");
+        assert!(similarity >= 0.95, "similarity {}", similarity);
+    }
+    
+    #[tokio::test]
+    async fn a_prompt_below_the_threshold_misses() {
+        let cache = cache();
+        store(&cache).await;
+        
+        let vector = cache.embed("far", &InferParams::default()).await.unwrap();
+        assert!(cache.lookup("summarize", "0:", &vector).is_none());
+    }
+    
+    #[tokio::test]
+    async fn a_slow_embedder_falls_back_to_a_miss() {
+        let mut cache = cache();
+        store(&cache).await;
+        
+        cache.embedder = embedder(Duration::from_secs(5));
+        let started = Instant::now();
+        assert!(cache.embed("close", &InferParams::default()).await.is_none());
+        assert!(started.elapsed() < Duration::from_secs(1), "gave up after embed_timeout_ms");
+    }
+}
@@ -19,6 +19,30 @@ pub struct Config {
     pub judge: JudgeConfig,
     #[serde(default)]
     pub tenancy: HashMap<String, TenantConfig>,
+    #[serde(default)]
+    pub idempotency: IdempotencyConfig,
+    #[serde(default)]
+    pub admin: AdminConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+    #[serde(default)]
+    pub validation: ValidationConfig,
+    #[serde(default)]
+    pub tasks: HashMap<String, TaskConfig>,
+    #[serde(default)]
+    pub embeddings: EmbeddingsConfig,
+    #[serde(default)]
+    pub warmup: WarmupConfig,
+    #[serde(default)]
+    pub residency: ResidencyConfig,
+    #[serde(default)]
+    pub latency: LatencyConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub jobs: JobsConfig,
+    #[serde(default)]
+    pub shadow: ShadowConfig,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -41,8 +65,15 @@ pub struct QoSConfig {
     pub hedge_after_ms: i32,
     #[serde(default = "default_max_prompt_bytes")]
     pub max_prompt_bytes: usize,
+    /// Decoded size of all image attachments in one request
+    #[serde(default = "default_max_image_bytes")]
+    pub max_image_bytes: usize,
     #[serde(default = "default_max_tokens")]
     pub max_tokens_default: i32,
+    #[serde(default = "default_max_deadline")]
+    pub deadline_default_ms: i32,
+    #[serde(default = "default_true")]
+    pub coalesce_inflight: bool,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
@@ -71,21 +102,199 @@ pub struct CacheConfig {
     pub driver: String,
     #[serde(default = "default_ttl")]
     pub ttl_seconds: u64,
+    /// How long past `ttl_seconds` an answer is kept to serve when every
+    /// provider fails; 0 disables stale-if-error
+    #[serde(default)]
+    pub stale_if_error_seconds: u64,
     #[serde(default)]
     pub key_fields: Vec<String>,
+    #[serde(default = "default_redis_url")]
+    pub redis_url: String,
+    #[serde(default = "default_redis_pool_size")]
+    pub redis_pool_size: usize,
+    #[serde(default = "default_redis_timeout")]
+    pub redis_timeout_ms: u64,
+    #[serde(default = "default_redis_key_prefix")]
+    pub redis_key_prefix: String,
+    #[serde(default)]
+    pub semantic: SemanticCacheConfig,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct SemanticCacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Provider whose embedding endpoint vectorizes prompts
+    pub embed_provider: Option<String>,
+    /// Minimum cosine similarity per task; tasks not listed skip this tier
+    #[serde(default)]
+    pub thresholds: HashMap<String, f32>,
+    #[serde(default = "default_semantic_max_entries")]
+    pub max_entries_per_task: usize,
+    #[serde(default = "default_semantic_embed_timeout")]
+    pub embed_timeout_ms: u64,
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ProviderConfig {
     pub name: String,
     pub driver: String,
+    #[serde(default)]
     pub base_url: String,
+    /// Replicas of the model, load-balanced as one provider; replaces
+    /// `base_url` when set
+    #[serde(default)]
+    pub endpoints: Vec<String>,
+    /// How calls are spread over `endpoints`: "least_outstanding" or
+    /// "round_robin"
+    #[serde(default = "default_balance")]
+    pub balance: String,
+    /// Send the same prompt to the same endpoint (consistent hashing) so its
+    /// engine can reuse the cached prefix
+    #[serde(default)]
+    pub prompt_affinity: bool,
     pub model: String,
     pub tasks: Vec<String>,
     #[serde(default = "default_weight")]
     pub weight: f32,
     #[serde(default)]
     pub timeout_ms: Option<i32>,
+    /// Constrained decoding for tasks with an output schema: "schema" sends
+    /// the schema itself, "json" only forces JSON, "none" disables it
+    #[serde(default = "default_structured_output")]
+    pub structured_output: String,
+    /// Sampling used when neither the request nor the task sets a value
+    #[serde(default)]
+    pub sampling: Sampling,
+    #[serde(default)]
+    pub limits: SamplingLimits,
+    /// Features beyond text generation: "embed", "vision"
+    #[serde(default)]
+    pub capabilities: Vec<String>,
+    /// Concurrent calls allowed to this provider; 0 means no limit
+    #[serde(default)]
+    pub max_concurrency: usize,
+    /// Calls that may wait for a slot before new ones are shed
+    #[serde(default = "default_max_queue")]
+    pub max_queue: usize,
+    /// How long the engine keeps the model loaded after a request, e.g.
+    /// "30m"; a negative duration keeps it loaded indefinitely
+    #[serde(default)]
+    pub keep_alive: Option<String>,
+    /// Candidate model that gets a copy of live traffic without serving it
+    #[serde(default)]
+    pub shadow: Option<ProviderShadow>,
+}
+
+impl ProviderConfig {
+    /// Base URLs the provider's calls go to: `endpoints`, or `base_url` alone.
+    pub fn endpoint_urls(&self) -> Vec<&str> {
+        if self.endpoints.is_empty() {
+            vec![self.base_url.as_str()]
+        } else {
+            self.endpoints.iter().map(String::as_str).collect()
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ProviderShadow {
+    /// Tasks whose answers are mirrored; the provider must not serve them
+    pub tasks: Vec<String>,
+    /// Share of fresh answers mirrored, 0-100
+    #[serde(default = "default_shadow_sample")]
+    pub sample_percent: f64,
+}
+
+/// Generation settings. Unset fields fall through request → task →
+/// provider defaults → engine defaults.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize, Serialize)]
+pub struct Sampling {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_p: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub top_k: Option<i32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub stop: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repeat_penalty: Option<f32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num_ctx: Option<i32>,
+}
+
+impl Sampling {
+    pub fn is_empty(&self) -> bool {
+        *self == Sampling::default()
+    }
+    
+    /// Fills every unset field from `fallback`.
+    pub fn or(&self, fallback: &Sampling) -> Sampling {
+        Sampling {
+            temperature: self.temperature.or(fallback.temperature),
+            top_p: self.top_p.or(fallback.top_p),
+            top_k: self.top_k.or(fallback.top_k),
+            seed: self.seed.or(fallback.seed),
+            stop: if self.stop.is_empty() { fallback.stop.clone() } else { self.stop.clone() },
+            repeat_penalty: self.repeat_penalty.or(fallback.repeat_penalty),
+            num_ctx: self.num_ctx.or(fallback.num_ctx),
+        }
+    }
+    
+    /// Rejects values no engine accepts; provider limits are applied later.
+    pub fn check(&self) -> std::result::Result<(), String> {
+        if self.temperature.is_some_and(|t| t < 0.0) {
+            return Err("temperature must not be negative".to_string());
+        }
+        if self.top_p.is_some_and(|p| !(0.0..=1.0).contains(&p)) {
+            return Err("top_p must be between 0 and 1".to_string());
+        }
+        if self.top_k.is_some_and(|k| k < 0) {
+            return Err("top_k must not be negative".to_string());
+        }
+        if self.repeat_penalty.is_some_and(|r| r < 0.0) {
+            return Err("repeat_penalty must not be negative".to_string());
+        }
+        if self.num_ctx.is_some_and(|n| n <= 0) {
+            return Err("num_ctx must be positive".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Inclusive `[min, max]` bounds a provider clamps sampling values into.
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct SamplingLimits {
+    pub temperature: Option<[f32; 2]>,
+    pub top_p: Option<[f32; 2]>,
+    pub top_k: Option<[i32; 2]>,
+    pub repeat_penalty: Option<[f32; 2]>,
+    pub num_ctx: Option<[i32; 2]>,
+    /// Most stop sequences passed on; extras are dropped
+    pub max_stop: Option<usize>,
+}
+
+impl SamplingLimits {
+    pub fn apply(&self, mut sampling: Sampling) -> Sampling {
+        fn clamp<T: PartialOrd + Copy>(value: Option<T>, bounds: Option<[T; 2]>) -> Option<T> {
+            let (Some(v), Some([lo, hi])) = (value, bounds) else {
+                return value;
+            };
+            Some(if v < lo { lo } else if v > hi { hi } else { v })
+        }
+        sampling.temperature = clamp(sampling.temperature, self.temperature);
+        sampling.top_p = clamp(sampling.top_p, self.top_p);
+        sampling.top_k = clamp(sampling.top_k, self.top_k);
+        sampling.repeat_penalty = clamp(sampling.repeat_penalty, self.repeat_penalty);
+        sampling.num_ctx = clamp(sampling.num_ctx, self.num_ctx);
+        if let Some(max) = self.max_stop {
+            sampling.stop.truncate(max);
+        }
+        sampling
+    }
 }
 
 #[derive(Debug, Clone, Deserialize, Serialize, Default)]
@@ -99,11 +308,300 @@ pub struct JudgeConfig {
     pub fallback_strategy: String,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct IdempotencyConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_idempotency_ttl")]
+    pub ttl_seconds: u64,
+    #[serde(default = "default_idempotency_max_entries")]
+    pub max_entries: u64,
+}
+
+impl Default for IdempotencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ttl_seconds: default_idempotency_ttl(),
+            max_entries: default_idempotency_max_entries(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TemplatesConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_templates_dir")]
+    pub dir: String,
+    /// Template file stem per task, for tasks whose name differs
+    #[serde(default)]
+    pub by_task: HashMap<String, String>,
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: default_templates_dir(),
+            by_task: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EmbeddingsConfig {
+    /// Most inputs accepted in one Embed call
+    #[serde(default = "default_embed_max_inputs")]
+    pub max_inputs: usize,
+    #[serde(default = "default_embed_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+    #[serde(default = "default_embed_cache_entries")]
+    pub cache_max_entries: u64,
+}
+
+impl Default for EmbeddingsConfig {
+    fn default() -> Self {
+        Self {
+            max_inputs: default_embed_max_inputs(),
+            cache_ttl_seconds: default_embed_cache_ttl(),
+            cache_max_entries: default_embed_cache_entries(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct WarmupConfig {
+    /// Load every model at startup; /health reports not ready until done
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Budget for one model load, well above the normal request timeout
+    #[serde(default = "default_warmup_timeout")]
+    pub timeout_ms: u64,
+    /// Ping the providers of `keep_warm_tasks` this often; 0 disables it
+    #[serde(default)]
+    pub keep_warm_interval_seconds: u64,
+    #[serde(default)]
+    pub keep_warm_tasks: Vec<String>,
+}
+
+impl Default for WarmupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            timeout_ms: default_warmup_timeout(),
+            keep_warm_interval_seconds: 0,
+            keep_warm_tasks: Vec::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ResidencyConfig {
+    /// Poll each host's loaded models and prefer providers already in memory
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    #[serde(default = "default_residency_refresh")]
+    pub refresh_interval_ms: u64,
+    /// Most distinct models one ensemble may keep loaded on a host, counting
+    /// the ones already there; 0 means no cap
+    #[serde(default)]
+    pub max_models_per_host: usize,
+}
+
+impl Default for ResidencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            refresh_interval_ms: default_residency_refresh(),
+            max_models_per_host: 0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ShadowConfig {
+    /// JSONL evaluation log with one line per shadow call; unset logs nothing
+    #[serde(default)]
+    pub log_path: Option<String>,
+    /// Token overlap with the winner at which a shadow answer counts as
+    /// agreeing; identical answers always agree
+    #[serde(default = "default_shadow_agreement")]
+    pub agreement_threshold: f64,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            log_path: None,
+            agreement_threshold: default_shadow_agreement(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct HealthConfig {
+    /// How often endpoints of multi-endpoint providers are probed; unhealthy
+    /// ones are tried last. 0 probes only when /health is called
+    #[serde(default = "default_health_interval")]
+    pub interval_ms: u64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self { interval_ms: default_health_interval() }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobsConfig {
+    /// Accept batch jobs on /v1/jobs
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Where job queries, progress and results are kept across restarts
+    #[serde(default = "default_jobs_dir")]
+    pub dir: String,
+    /// Queries of one job in flight at once; jobs themselves run one at a time
+    #[serde(default = "default_jobs_concurrency")]
+    pub concurrency: usize,
+    /// Per-query deadline when neither the job nor the query sets one;
+    /// 0 takes `qos.max_deadline_ms`
+    #[serde(default)]
+    pub deadline_ms: i32,
+    /// Tries per query while it fails for transient reasons
+    #[serde(default = "default_jobs_max_attempts")]
+    pub max_attempts: u32,
+    /// Wait before a retry, multiplied by the attempt number
+    #[serde(default = "default_jobs_retry_backoff")]
+    pub retry_backoff_ms: u64,
+    #[serde(default = "default_jobs_max_queries")]
+    pub max_queries: usize,
+    /// Hosts a job's `callback_url` may point at; jobs with a callback to
+    /// any other host are refused, and none are accepted while empty
+    #[serde(default)]
+    pub callback_hosts: Vec<String>,
+    /// Finished jobs and their files are deleted this long after they end;
+    /// 0 keeps them forever
+    #[serde(default = "default_jobs_retention")]
+    pub retention_hours: u64,
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            dir: default_jobs_dir(),
+            concurrency: default_jobs_concurrency(),
+            deadline_ms: 0,
+            max_attempts: default_jobs_max_attempts(),
+            retry_backoff_ms: default_jobs_retry_backoff(),
+            max_queries: default_jobs_max_queries(),
+            callback_hosts: Vec::new(),
+            retention_hours: default_jobs_retention(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct LatencyConfig {
+    /// Track latency per provider and task and try the fastest expected
+    /// provider first under FASTEST
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Weight of the newest sample in the moving average
+    #[serde(default = "default_latency_alpha")]
+    pub ewma_alpha: f64,
+    /// Recent samples kept for the p95
+    #[serde(default = "default_latency_window")]
+    pub window: usize,
+}
+
+impl Default for LatencyConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            ewma_alpha: default_latency_alpha(),
+            window: default_latency_window(),
+        }
+    }
+}
+
+/// Defaults for one task, overridden by whatever the request sets.
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct TaskConfig {
+    pub max_tokens: Option<i32>,
+    pub deadline_ms: Option<i32>,
+    #[serde(flatten)]
+    pub sampling: Sampling,
+    pub strategy: Option<String>,
+    pub cache_ttl_seconds: Option<u64>,
+    /// Template file stem in the templates dir
+    pub template: Option<String>,
+    /// JSON Schema file for the output
+    pub output_schema: Option<String>,
+    /// Providers that serve the task, in addition to their own `tasks` lists
+    #[serde(default)]
+    pub providers: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct ValidationConfig {
+    /// Re-ask a provider once with the validation error if time allows
+    #[serde(default = "default_true")]
+    pub reask: bool,
+    /// JSON Schema file per task
+    #[serde(default)]
+    pub schemas: HashMap<String, String>,
+}
+
+impl Default for ValidationConfig {
+    fn default() -> Self {
+        Self {
+            reask: true,
+            schemas: HashMap::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize, Default)]
+pub struct AdminConfig {
+    /// Credential for `/admin/*`, sent as `X-Admin-Key`; the admin API is
+    /// disabled while unset
+    pub api_key: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct TenantConfig {
     pub api_key: String,
     pub rate_limit_rps: u32,
     pub rate_limit_burst: u32,
+    /// Class for this tenant's requests that do not set one
+    #[serde(default)]
+    pub default_priority: Priority,
+    /// Share of a busy provider relative to other tenants in the same class
+    #[serde(default = "default_weight")]
+    pub weight: f32,
+}
+
+/// Scheduling class in provider queues. Declared in serving order, so the
+/// derived ordering puts `Realtime` first.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Priority {
+    Realtime,
+    #[default]
+    Normal,
+    Batch,
+}
+
+impl Priority {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Priority::Realtime => "realtime",
+            Priority::Normal => "normal",
+            Priority::Batch => "batch",
+        }
+    }
 }
 
 // Defaults
@@ -113,6 +611,7 @@ fn default_true() -> bool { true }
 fn default_max_deadline() -> i32 { 1500 }
 fn default_hedge_after() -> i32 { 300 }
 fn default_max_prompt_bytes() -> usize { 16384 }
+fn default_max_image_bytes() -> usize { 8 * 1024 * 1024 }
 fn default_max_tokens() -> i32 { 256 }
 fn default_strategy() -> String { "FASTEST".to_string() }
 fn default_fail_rate() -> f32 { 0.10 }
@@ -120,10 +619,38 @@ fn default_window_size() -> usize { 50 }
 fn default_cooldown() -> u64 { 300000 }
 fn default_cache_driver() -> String { "memory".to_string() }
 fn default_ttl() -> u64 { 900 }
+fn default_redis_url() -> String { "redis://127.0.0.1:6379".to_string() }
+fn default_redis_pool_size() -> usize { 4 }
+fn default_redis_timeout() -> u64 { 50 }
+fn default_redis_key_prefix() -> String { "llmpool:".to_string() }
+fn default_semantic_max_entries() -> usize { 1000 }
+fn default_semantic_embed_timeout() -> u64 { 150 }
 fn default_weight() -> f32 { 1.0 }
 fn default_judge_max_tokens() -> i32 { 128 }
 fn default_judge_deadline() -> i32 { 700 }
 fn default_fallback_strategy() -> String { "VOTING".to_string() }
+fn default_idempotency_ttl() -> u64 { 300 }
+fn default_templates_dir() -> String { "prompts".to_string() }
+fn default_structured_output() -> String { "schema".to_string() }
+fn default_embed_max_inputs() -> usize { 256 }
+fn default_embed_cache_ttl() -> u64 { 86400 }
+fn default_embed_cache_entries() -> u64 { 50000 }
+fn default_warmup_timeout() -> u64 { 60000 }
+fn default_residency_refresh() -> u64 { 2000 }
+fn default_health_interval() -> u64 { 5000 }
+fn default_max_queue() -> usize { 32 }
+fn default_balance() -> String { "least_outstanding".to_string() }
+fn default_latency_alpha() -> f64 { 0.3 }
+fn default_latency_window() -> usize { 100 }
+fn default_shadow_sample() -> f64 { 100.0 }
+fn default_shadow_agreement() -> f64 { 0.8 }
+fn default_jobs_dir() -> String { "jobs".to_string() }
+fn default_jobs_concurrency() -> usize { 4 }
+fn default_jobs_max_attempts() -> u32 { 5 }
+fn default_jobs_retry_backoff() -> u64 { 2000 }
+fn default_jobs_max_queries() -> usize { 100000 }
+fn default_jobs_retention() -> u64 { 168 }
+fn default_idempotency_max_entries() -> u64 { 100000 }
 
 pub fn load<P: AsRef<Path>>(path: P) -> Result<Config> {
     let content = std::fs::read_to_string(path)
@@ -141,6 +668,19 @@ fn validate(config: &Config) -> Result<()> {
         return Err(LLMPoolError::ConfigError("No providers configured".to_string()));
     }
     
+    for provider in &config.providers {
+        if provider.base_url.is_empty() && provider.endpoints.is_empty() {
+            return Err(LLMPoolError::ConfigError(
+                format!("Provider {} needs base_url or endpoints", provider.name)
+            ));
+        }
+        if !matches!(provider.balance.as_str(), "least_outstanding" | "round_robin") {
+            return Err(LLMPoolError::ConfigError(
+                format!("Provider {}: unknown balance {}", provider.name, provider.balance)
+            ));
+        }
+    }
+    
     if config.qos.max_deadline_ms <= 0 {
         return Err(LLMPoolError::ConfigError("max_deadline_ms must be positive".to_string()));
     }
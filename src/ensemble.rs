@@ -1,8 +1,10 @@
 use crate::config::Config;
 use crate::errors::{LLMPoolError, Result};
-use crate::providers::{Provider, ProviderResponse};
+use crate::providers::{ChatMessage, InferParams, PromptInput, Provider, ProviderResponse};
+use crate::validation::{OutputSchema, ValidationOutcome, ValidationReport};
 use std::sync::Arc;
-use tracing::info;
+use std::time::Instant;
+use tracing::{info, warn};
 
 #[derive(Debug, Clone)]
 pub enum Strategy {
@@ -34,6 +36,105 @@ pub struct EnsembleResult {
     pub models_queried: Vec<String>,
     pub model_scores: Vec<f32>,
     pub reason: String,
+    /// Set when the task has an output schema
+    pub validation: Option<ValidationReport>,
+}
+
+/// Schema context shared by every provider call in one ensemble run.
+#[derive(Clone)]
+pub struct OutputCheck {
+    pub schema: Arc<OutputSchema>,
+    pub reask: bool,
+}
+
+/// One provider's answer after the schema check.
+enum Attempt {
+    Accepted {
+        response: ProviderResponse,
+        outcome: ValidationOutcome,
+        reasks: u32,
+    },
+    Rejected {
+        model: String,
+        error: String,
+        reasks: u32,
+    },
+}
+
+impl Attempt {
+    fn reasks(&self) -> u32 {
+        match self {
+            Attempt::Accepted { reasks, .. } | Attempt::Rejected { reasks, .. } => *reasks,
+        }
+    }
+}
+
+/// Calls a provider and, with an output schema, validates the answer. An
+/// invalid answer is re-asked once with the errors if the first call left
+/// enough of `params.deadline_ms` for another one.
+async fn attempt(
+    provider: &dyn Provider,
+    input: &PromptInput,
+    params: &InferParams,
+    check: Option<&OutputCheck>,
+) -> Result<Attempt> {
+    let started = Instant::now();
+    let response = input.send(provider, params).await?;
+    let Some(check) = check else {
+        return Ok(Attempt::Accepted { response, outcome: ValidationOutcome::Valid, reasks: 0 });
+    };
+    
+    let error = match check.schema.check(&response.content) {
+        Ok((content, repaired)) => {
+            let outcome = if repaired { ValidationOutcome::Repaired } else { ValidationOutcome::Valid };
+            return Ok(Attempt::Accepted {
+                response: ProviderResponse { content, ..response },
+                outcome,
+                reasks: 0,
+            });
+        }
+        Err(error) => error,
+    };
+    
+    let elapsed = started.elapsed().as_millis() as i32;
+    if !check.reask || elapsed + response.duration_ms > params.deadline_ms {
+        warn!("📐 {} output failed schema validation: {}", response.model, error);
+        return Ok(Attempt::Rejected { model: response.model, error, reasks: 0 });
+    }
+    
+    info!("📐 Re-asking {} after schema validation failed: {}", response.model, error);
+    let correction = format!(
+        "Sua resposta anterior não passou na validação do JSON Schema: {}\nResponda novamente somente com o JSON corrigido.",
+        error
+    );
+    let reask = match input {
+        PromptInput::Text(prompt) => {
+            PromptInput::Text(format!("{}\n\n## Correção\n{}", prompt, correction))
+        }
+        PromptInput::Messages(messages) => {
+            let mut messages = messages.clone();
+            messages.push(ChatMessage::new("assistant", response.content.clone()));
+            messages.push(ChatMessage::new("user", correction));
+            PromptInput::Messages(messages)
+        }
+    };
+    let retry_params = InferParams {
+        deadline_ms: params.deadline_ms - elapsed,
+        ..params.clone()
+    };
+    let retry = reask.send(provider, &retry_params).await?;
+    
+    match check.schema.check(&retry.content) {
+        Ok((content, _)) => Ok(Attempt::Accepted {
+            response: ProviderResponse { content, ..retry },
+            outcome: ValidationOutcome::Reasked,
+            reasks: 1,
+        }),
+        Err(error) => {
+            warn!("📐 {} output failed schema validation after re-ask: {}", retry.model, error);
+            Ok(Attempt::Rejected { model: retry.model, error, reasks: 1 })
+        }
+    }
 }
 
 pub struct Ensemble {
@@ -50,52 +151,90 @@ impl Ensemble {
         &self,
         strategy: Strategy,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         if providers.is_empty() {
             return Err(LLMPoolError::EnsembleError("No providers available".to_string()));
         }
         
         match strategy {
-            Strategy::Fastest => self.fastest(providers, prompt, max_tokens, deadline_ms).await,
-            Strategy::Voting => self.voting(providers, prompt, max_tokens, deadline_ms).await,
-            Strategy::Weighted => self.weighted(providers, prompt, max_tokens, deadline_ms).await,
-            Strategy::Consensus => self.consensus(providers, prompt, max_tokens, deadline_ms).await,
-            Strategy::Judge => self.judge(providers, prompt, max_tokens, deadline_ms).await,
+            Strategy::Fastest => self.fastest(providers, input, params, check).await,
+            Strategy::Voting => self.voting(providers, input, params, check).await,
+            Strategy::Weighted => self.weighted(providers, input, params, check).await,
+            Strategy::Consensus => self.consensus(providers, input, params, check).await,
+            Strategy::Judge => self.judge(providers, input, params, check).await,
         }
     }
     
     async fn fastest(
         &self,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         info!("🏃 FASTEST strategy with {} providers", providers.len());
         
-        // For now, just use the first provider
+        // For now, use the first provider whose output passes the schema
         // TODO: Implement hedged requests and race condition
-        let provider = &providers[0];
-        let response = provider.infer(prompt, max_tokens, deadline_ms).await?;
+        let started = Instant::now();
+        let mut rejected = Vec::new();
+        let mut reasks = 0;
+        let mut shed = None;
+        for provider in &providers {
+            // Each try gets what the ones before it left of the deadline
+            let params = match params.deadline_ms {
+                0 => params.clone(),
+                deadline_ms => {
+                    let left_ms = deadline_ms - started.elapsed().as_millis() as i32;
+                    if left_ms <= 0 {
+                        return Err(LLMPoolError::DeadlineExceeded(deadline_ms));
+                    }
+                    InferParams { deadline_ms: left_ms, ..params.clone() }
+                }
+            };
+            
+            // A provider with no free slot sheds the call; try the next one
+            let result = match attempt(provider.as_ref(), input, &params, check.as_ref()).await {
+                Err(e @ LLMPoolError::Overloaded(_)) => {
+                    shed = Some(e);
+                    continue;
+                }
+                other => other?,
+            };
+            match result {
+                Attempt::Accepted { response, outcome, reasks: n } => {
+                    reasks += n;
+                    return Ok(EnsembleResult {
+                        response: response.clone(),
+                        strategy_used: Strategy::Fastest,
+                        models_queried: vec![response.model.clone()],
+                        model_scores: vec![1.0],
+                        reason: "First provider to respond".to_string(),
+                        validation: check.as_ref().map(|_| ValidationReport { outcome, reasks, rejected }),
+                    });
+                }
+                Attempt::Rejected { model, error, reasks: n } => {
+                    reasks += n;
+                    rejected.push(format!("{}: {}", model, error));
+                }
+            }
+        }
         
-        Ok(EnsembleResult {
-            response: response.clone(),
-            strategy_used: Strategy::Fastest,
-            models_queried: vec![response.model.clone()],
-            model_scores: vec![1.0],
-            reason: "First provider to respond".to_string(),
-        })
+        match shed {
+            Some(e) if rejected.is_empty() => Err(e),
+            _ => Err(schema_failure(&rejected)),
+        }
     }
     
     async fn voting(
         &self,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         info!("🗳️  VOTING strategy with {} providers", providers.len());
         
@@ -103,20 +242,45 @@ impl Ensemble {
         let mut tasks = Vec::new();
         for provider in providers.iter() {
             let p = provider.clone();
-            let prompt = prompt.to_string();
+            let input = input.clone();
+            let params = params.clone();
+            let check = check.clone();
             tasks.push(tokio::spawn(async move {
-                p.infer(&prompt, max_tokens, deadline_ms).await
+                attempt(p.as_ref(), &input, &params, check.as_ref()).await
             }));
         }
         
         let mut responses = Vec::new();
+        let mut outcomes = Vec::new();
+        let mut rejected = Vec::new();
+        let mut reasks = 0;
+        let mut shed = None;
         for task in tasks {
-            if let Ok(Ok(resp)) = task.await {
-                responses.push(resp);
+            match task.await {
+                Ok(Ok(attempt)) => {
+                    reasks += attempt.reasks();
+                    match attempt {
+                        Attempt::Accepted { response, outcome, .. } => {
+                            responses.push(response);
+                            outcomes.push(outcome);
+                        }
+                        Attempt::Rejected { model, error, .. } => {
+                            rejected.push(format!("{}: {}", model, error));
+                        }
+                    }
+                }
+                Ok(Err(e @ LLMPoolError::Overloaded(_))) => shed = Some(e),
+                _ => {}
             }
         }
         
         if responses.is_empty() {
+            if !rejected.is_empty() {
+                return Err(schema_failure(&rejected));
+            }
+            if let Some(e) = shed {
+                return Err(e);
+            }
             return Err(LLMPoolError::EnsembleError("All providers failed".to_string()));
         }
         
@@ -129,44 +293,45 @@ impl Ensemble {
             models_queried: responses.iter().map(|r| r.model.clone()).collect(),
             model_scores: vec![1.0; responses.len()],
             reason: "Voting consensus".to_string(),
+            validation: check.as_ref().map(|_| ValidationReport { outcome: outcomes[0], reasks, rejected }),
         })
     }
     
     async fn weighted(
         &self,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         info!("⚖️  WEIGHTED strategy with {} providers", providers.len());
         // For now, fallback to fastest
-        self.fastest(providers, prompt, max_tokens, deadline_ms).await
+        self.fastest(providers, input, params, check).await
     }
     
     async fn consensus(
         &self,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         info!("🤝 CONSENSUS strategy with {} providers", providers.len());
         // For now, fallback to voting
-        self.voting(providers, prompt, max_tokens, deadline_ms).await
+        self.voting(providers, input, params, check).await
     }
     
     async fn judge(
         &self,
         providers: Vec<Arc<dyn Provider>>,
-        prompt: &str,
-        max_tokens: i32,
-        deadline_ms: i32,
+        input: &PromptInput,
+        params: &InferParams,
+        check: Option<OutputCheck>,
     ) -> Result<EnsembleResult> {
         info!("⚖️  JUDGE strategy with {} providers", providers.len());
         
         // Get multiple candidates
-        let voting_result = self.voting(providers, prompt, max_tokens, deadline_ms).await?;
+        let voting_result = self.voting(providers, input, params, check).await?;
         
         // TODO: Implement actual judge logic with a separate model
         // For now, return the voting result
@@ -176,3 +341,10 @@ impl Ensemble {
         })
     }
 }
+
+fn schema_failure(rejected: &[String]) -> LLMPoolError {
+    LLMPoolError::EnsembleError(format!(
+        "No candidate passed output schema validation: {}",
+        rejected.join(" | ")
+    ))
+}
@@ -1,6 +1,6 @@
 use thiserror::Error;
 
-#[derive(Error, Debug)]
+#[derive(Error, Debug, Clone)]
 pub enum LLMPoolError {
     #[error("Invalid query: {0}")]
     InvalidQuery(String),
@@ -11,6 +11,9 @@ pub enum LLMPoolError {
     #[error("Provider error: {0}")]
     ProviderError(String),
 
+    #[error("Provider unavailable: {0}")]
+    ProviderUnavailable(String),
+
     #[error("Ensemble error: {0}")]
     EnsembleError(String),
 
@@ -23,12 +26,18 @@ pub enum LLMPoolError {
     #[error("Rate limit exceeded")]
     RateLimitExceeded,
 
+    #[error("Overloaded: {0}")]
+    Overloaded(String),
+
     #[error("Circuit breaker open for provider: {0}")]
     CircuitBreakerOpen(String),
 
     #[error("Cache error: {0}")]
     CacheError(String),
 
+    #[error("Conflict: {0}")]
+    Conflict(String),
+
     #[error("Internal error: {0}")]
     Internal(String),
 }
@@ -48,6 +57,12 @@ impl From<LLMPoolError> for tonic::Status {
             LLMPoolError::RateLimitExceeded => {
                 tonic::Status::resource_exhausted("Rate limit exceeded")
             }
+            LLMPoolError::Conflict(msg) => {
+                tonic::Status::already_exists(msg)
+            }
+            LLMPoolError::Overloaded(msg) | LLMPoolError::ProviderUnavailable(msg) => {
+                tonic::Status::unavailable(msg)
+            }
             _ => tonic::Status::internal(err.to_string()),
         }
     }
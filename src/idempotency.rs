@@ -0,0 +1,131 @@
+use crate::config::IdempotencyConfig;
+use crate::errors::{LLMPoolError, Result};
+use crate::orchestrator::{InferRequest, InferResponse};
+use moka::future::Cache as MokaCache;
+use moka::ops::compute::Op;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
+
+struct Slot {
+    fingerprint: String,
+    answer: watch::Receiver<Option<InferResponse>>,
+}
+
+pub enum Claim {
+    /// First request for this id; run it and report through the guard
+    Owner(ClaimGuard),
+    /// A duplicate of a request that already finished
+    Replay(Box<InferResponse>),
+}
+
+/// Held by the request that owns a `(tenant, request_id)` slot. Dropping it
+/// without `complete` (error or cancellation) releases the slot so that a
+/// retry runs again instead of waiting forever.
+pub struct ClaimGuard {
+    tx: watch::Sender<Option<InferResponse>>,
+}
+
+impl ClaimGuard {
+    pub fn complete(self, response: &InferResponse) {
+        self.tx.send_replace(Some(response.clone()));
+    }
+}
+
+/// Short-lived store of answers keyed by `(tenant, request_id)`, so client
+/// retries get the original answer instead of a fresh ensemble run.
+pub struct IdempotencyStore {
+    slots: MokaCache<String, Arc<Slot>>,
+}
+
+impl IdempotencyStore {
+    pub fn new(config: &IdempotencyConfig) -> Self {
+        let slots = MokaCache::builder()
+            .time_to_live(Duration::from_secs(config.ttl_seconds))
+            .max_capacity(config.max_entries)
+            .build();
+        
+        Self { slots }
+    }
+    
+    /// Claims the request id, or waits for its owner and returns the same
+    /// answer. A different payload under a known id is a conflict.
+    pub async fn claim(&self, req: &InferRequest) -> Result<Claim> {
+        let key = format!("{}:{}", req.tenant_id, req.request_id);
+        let fingerprint = fingerprint(req);
+        
+        loop {
+            let (tx, rx) = watch::channel(None);
+            let entry = self.slots
+                .entry(key.clone())
+                .or_insert_with(async {
+                    Arc::new(Slot {
+                        fingerprint: fingerprint.clone(),
+                        answer: rx,
+                    })
+                })
+                .await;
+            
+            if entry.is_fresh() {
+                return Ok(Claim::Owner(ClaimGuard { tx }));
+            }
+            
+            let slot = entry.into_value();
+            if slot.fingerprint != fingerprint {
+                return Err(LLMPoolError::Conflict(format!(
+                    "request_id {} was already used with a different payload",
+                    req.request_id
+                )));
+            }
+            
+            let mut answer = slot.answer.clone();
+            if let Ok(done) = answer.wait_for(Option::is_some).await {
+                if let Some(response) = done.clone() {
+                    return Ok(Claim::Replay(Box::new(response)));
+                }
+            }
+            
+            // The owner failed or went away; clear its slot unless someone
+            // already replaced it, then compete for a fresh claim.
+            self.slots
+                .entry(key.clone())
+                .and_compute_with(|current| {
+                    let op = match current {
+                        Some(e) if Arc::ptr_eq(e.value(), &slot) => Op::Remove,
+                        _ => Op::Nop,
+                    };
+                    std::future::ready(op)
+                })
+                .await;
+        }
+    }
+}
+
+/// Hash of the fields that define the answer. The deadline is left out on
+/// purpose: retries usually carry whatever budget the client has left.
+fn fingerprint(req: &InferRequest) -> String {
+    use sha2::{Sha256, Digest};
+    let mut hasher = Sha256::new();
+    hasher.update(req.project_id.as_bytes());
+    hasher.update([0]);
+    hasher.update(req.task.as_bytes());
+    hasher.update([0]);
+    hasher.update(req.prompt.as_bytes());
+    hasher.update([0]);
+    hasher.update(serde_json::to_string(&req.messages).unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(req.input.as_deref().unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(req.output_schema.as_deref().unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(serde_json::to_string(&req.sampling).unwrap_or_default().as_bytes());
+    hasher.update([0]);
+    hasher.update(req.max_tokens.to_string().as_bytes());
+    hasher.update([0]);
+    hasher.update(req.strategy.as_deref().unwrap_or_default().as_bytes());
+    for image in &req.images {
+        hasher.update([0]);
+        hasher.update(image.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
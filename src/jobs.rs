@@ -0,0 +1,644 @@
+use crate::config::{Config, JobsConfig, Priority};
+use crate::errors::{LLMPoolError, Result};
+use crate::orchestrator::{InferRequest, Orchestrator};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::{Notify, Semaphore};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Completed,
+    /// Stopped by a storage error; results written so far are kept
+    Failed,
+}
+
+/// What is persisted about a job besides its queries and results.
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct JobMeta {
+    id: String,
+    state: JobState,
+    total: usize,
+    created_at: chrono::DateTime<chrono::Utc>,
+    finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    callback_url: Option<String>,
+    /// Why a `Failed` job stopped
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+struct Job {
+    meta: Mutex<JobMeta>,
+    succeeded: AtomicUsize,
+    failed: AtomicUsize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct JobStatus {
+    pub id: String,
+    pub state: JobState,
+    pub total: usize,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub pending: usize,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// One line of a job's results file.
+#[derive(Debug, Default, Deserialize, Serialize)]
+struct JobResult {
+    index: usize,
+    request_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    winner_model: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    strategy_used: Option<String>,
+    #[serde(default)]
+    from_cache: bool,
+    #[serde(default)]
+    duration_ms: i32,
+    #[serde(default)]
+    queue_ms: i32,
+    #[serde(default)]
+    attempts: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Job {
+    fn status(&self) -> JobStatus {
+        let meta = self.meta.lock().unwrap();
+        let succeeded = self.succeeded.load(Ordering::Relaxed);
+        let failed = self.failed.load(Ordering::Relaxed);
+        JobStatus {
+            id: meta.id.clone(),
+            state: meta.state,
+            total: meta.total,
+            succeeded,
+            failed,
+            pending: meta.total.saturating_sub(succeeded + failed),
+            created_at: meta.created_at,
+            finished_at: meta.finished_at,
+            error: meta.error.clone(),
+        }
+    }
+}
+
+/// Batch jobs: lists of queries run in the background at batch priority.
+/// Each job lives in `jobs.dir` as `<id>.json` (state), `<id>.queries.jsonl`
+/// and `<id>.results.jsonl`, so unfinished jobs pick up where they stopped
+/// after a restart.
+pub struct JobManager {
+    config: JobsConfig,
+    max_deadline_ms: i32,
+    dir: PathBuf,
+    orchestrator: Arc<Orchestrator>,
+    jobs: RwLock<HashMap<String, Arc<Job>>>,
+    queue: Mutex<VecDeque<String>>,
+    wake: Notify,
+    client: reqwest::Client,
+}
+
+impl JobManager {
+    /// Loads the jobs in `jobs.dir` and queues the unfinished ones.
+    pub fn open(config: &Config, orchestrator: Arc<Orchestrator>) -> Result<Self> {
+        let dir = PathBuf::from(&config.jobs.dir);
+        std::fs::create_dir_all(&dir).map_err(|e| {
+            LLMPoolError::ConfigError(format!("Cannot create jobs dir {}: {}", dir.display(), e))
+        })?;
+        
+        let manager = Self {
+            config: config.jobs.clone(),
+            max_deadline_ms: config.qos.max_deadline_ms,
+            dir,
+            orchestrator,
+            jobs: RwLock::new(HashMap::new()),
+            queue: Mutex::new(VecDeque::new()),
+            wake: Notify::new(),
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(10))
+                // A redirect could point the callback past the allow-list
+                .redirect(reqwest::redirect::Policy::none())
+                .build()
+                .expect("Failed to build HTTP client"),
+        };
+        manager.load()?;
+        Ok(manager)
+    }
+    
+    fn load(&self) -> Result<()> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| {
+            LLMPoolError::ConfigError(format!("Cannot read jobs dir {}: {}", self.dir.display(), e))
+        })?;
+        
+        let mut unfinished: Vec<JobMeta> = Vec::new();
+        let mut jobs = self.jobs.write().unwrap();
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+            let meta: JobMeta = match std::fs::read(&path)
+                .map_err(|e| e.to_string())
+                .and_then(|bytes| serde_json::from_slice(&bytes).map_err(|e| e.to_string()))
+            {
+                Ok(meta) => meta,
+                Err(e) => {
+                    warn!("📦 Skipping unreadable job file {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            
+            let (succeeded, failed) = read_results(&self.results_path(&meta.id))
+                .iter()
+                .fold((0, 0), |(ok, err), r| if r.error.is_some() { (ok, err + 1) } else { (ok + 1, err) });
+            if matches!(meta.state, JobState::Queued | JobState::Running) {
+                unfinished.push(meta.clone());
+            }
+            jobs.insert(meta.id.clone(), Arc::new(Job {
+                meta: Mutex::new(meta),
+                succeeded: AtomicUsize::new(succeeded),
+                failed: AtomicUsize::new(failed),
+            }));
+        }
+        
+        // Resume in submission order
+        unfinished.sort_by_key(|m| m.created_at);
+        info!("📦 Loaded {} batch jobs ({} unfinished)", jobs.len(), unfinished.len());
+        self.queue.lock().unwrap().extend(unfinished.into_iter().map(|m| m.id));
+        Ok(())
+    }
+    
+    /// Stores the queries and queues the job. Every query runs at batch
+    /// priority; ones without a deadline take the job's.
+    pub async fn submit(
+        &self,
+        mut queries: Vec<InferRequest>,
+        deadline_ms: Option<i32>,
+        callback_url: Option<String>,
+    ) -> Result<JobStatus> {
+        if queries.is_empty() {
+            return Err(LLMPoolError::InvalidQuery("A job needs at least one query".to_string()));
+        }
+        if queries.len() > self.config.max_queries {
+            return Err(LLMPoolError::InvalidQuery(format!(
+                "Job has {} queries, max is {}", queries.len(), self.config.max_queries
+            )));
+        }
+        if let Some(url) = &callback_url {
+            self.check_callback(url)?;
+        }
+        
+        let deadline_ms = deadline_ms
+            .filter(|&d| d > 0)
+            .unwrap_or(match self.config.deadline_ms {
+                0 => self.max_deadline_ms,
+                d => d,
+            });
+        let mut body = String::new();
+        for query in &mut queries {
+            query.priority = Some(Priority::Batch);
+            if query.deadline_ms == 0 {
+                query.deadline_ms = deadline_ms;
+            }
+            body.push_str(&serde_json::to_string(query).map_err(internal)?);
+            body.push('\n');
+        }
+        
+        let meta = JobMeta {
+            id: uuid::Uuid::new_v4().to_string(),
+            state: JobState::Queued,
+            total: queries.len(),
+            created_at: chrono::Utc::now(),
+            finished_at: None,
+            callback_url,
+            error: None,
+        };
+        tokio::fs::write(self.queries_path(&meta.id), body).await.map_err(internal)?;
+        self.save(&meta).await?;
+        
+        let job = Arc::new(Job {
+            meta: Mutex::new(meta.clone()),
+            succeeded: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+        });
+        self.jobs.write().unwrap().insert(meta.id.clone(), job.clone());
+        self.queue.lock().unwrap().push_back(meta.id.clone());
+        self.wake.notify_one();
+        
+        info!("📦 Batch job {} queued with {} queries", meta.id, meta.total);
+        Ok(job.status())
+    }
+    
+    /// Callbacks go out from inside the network, so only to allowed hosts.
+    fn check_callback(&self, url: &str) -> Result<()> {
+        let parsed = reqwest::Url::parse(url)
+            .ok()
+            .filter(|u| matches!(u.scheme(), "http" | "https"));
+        let Some(host) = parsed.as_ref().and_then(|u| u.host_str()) else {
+            return Err(LLMPoolError::InvalidQuery(format!("Invalid callback_url: {}", url)));
+        };
+        if !self.config.callback_hosts.iter().any(|allowed| allowed.eq_ignore_ascii_case(host)) {
+            return Err(LLMPoolError::InvalidQuery(format!(
+                "callback_url host {} is not in [jobs] callback_hosts", host
+            )));
+        }
+        Ok(())
+    }
+    
+    pub fn status(&self, id: &str) -> Option<JobStatus> {
+        self.jobs.read().unwrap().get(id).map(|job| job.status())
+    }
+    
+    /// The results written so far as JSONL, in query order.
+    pub async fn results(&self, id: &str) -> Option<String> {
+        if !self.jobs.read().unwrap().contains_key(id) {
+            return None;
+        }
+        let path = self.results_path(id);
+        let mut results = tokio::task::spawn_blocking(move || read_results(&path)).await.ok()?;
+        results.sort_by_key(|r| r.index);
+        
+        let mut body = String::new();
+        for result in results {
+            body.push_str(&serde_json::to_string(&result).ok()?);
+            body.push('\n');
+        }
+        Some(body)
+    }
+    
+    async fn run(&self, id: &str) -> Result<()> {
+        let Some(job) = self.jobs.read().unwrap().get(id).cloned() else {
+            return Ok(());
+        };
+        let meta = {
+            let mut meta = job.meta.lock().unwrap();
+            meta.state = JobState::Running;
+            meta.clone()
+        };
+        self.save(&meta).await?;
+        
+        let queries = tokio::fs::read_to_string(self.queries_path(id)).await.map_err(internal)?;
+        let results_path = self.results_path(id);
+        let done: HashSet<usize> = {
+            let path = results_path.clone();
+            tokio::task::spawn_blocking(move || read_results(&path))
+                .await
+                .map_err(internal)?
+                .into_iter()
+                .map(|r| r.index)
+                .collect()
+        };
+        if !done.is_empty() {
+            info!("📦 Resuming batch job {}: {}/{} done", id, done.len(), meta.total);
+        } else {
+            info!("📦 Running batch job {} ({} queries)", id, meta.total);
+        }
+        
+        let results = Arc::new(tokio::sync::Mutex::new(
+            tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&results_path)
+                .await
+                .map_err(internal)?,
+        ));
+        let slots = Arc::new(Semaphore::new(self.config.concurrency.max(1)));
+        let mut running = tokio::task::JoinSet::new();
+        
+        for (index, line) in queries.lines().enumerate() {
+            if done.contains(&index) {
+                continue;
+            }
+            let query: InferRequest = match serde_json::from_str(line) {
+                Ok(query) => query,
+                Err(e) => {
+                    let result = JobResult {
+                        index,
+                        error: Some(format!("Invalid query: {}", e)),
+                        ..Default::default()
+                    };
+                    record(&results, &job, result).await?;
+                    continue;
+                }
+            };
+            let permit = slots.clone().acquire_owned().await.map_err(internal)?;
+            // Stop at the first result that could not be written
+            while let Some(finished) = running.try_join_next() {
+                finished.map_err(internal)??;
+            }
+            let orchestrator = self.orchestrator.clone();
+            let config = self.config.clone();
+            let job = job.clone();
+            let results = results.clone();
+            running.spawn(async move {
+                let result = run_query(&orchestrator, &config, index, query).await;
+                let recorded = record(&results, &job, result).await;
+                drop(permit);
+                recorded
+            });
+        }
+        while let Some(finished) = running.join_next().await {
+            finished.map_err(internal)??;
+        }
+        results.lock().await.flush().await.map_err(internal)?;
+        
+        let meta = {
+            let mut meta = job.meta.lock().unwrap();
+            meta.state = JobState::Completed;
+            meta.finished_at = Some(chrono::Utc::now());
+            meta.clone()
+        };
+        self.save(&meta).await?;
+        
+        let status = job.status();
+        info!("✅ Batch job {} completed: {} succeeded, {} failed", id, status.succeeded, status.failed);
+        if let Some(url) = &meta.callback_url {
+            self.notify(url, &status).await;
+        }
+        Ok(())
+    }
+    
+    /// Marks a job that `run` gave up on, so it is not left running forever.
+    async fn fail(&self, id: &str, error: &LLMPoolError) {
+        warn!("📦 Batch job {} failed: {}", id, error);
+        let Some(job) = self.jobs.read().unwrap().get(id).cloned() else {
+            return;
+        };
+        let meta = {
+            let mut meta = job.meta.lock().unwrap();
+            meta.state = JobState::Failed;
+            meta.finished_at = Some(chrono::Utc::now());
+            meta.error = Some(error.to_string());
+            meta.clone()
+        };
+        if let Err(e) = self.save(&meta).await {
+            warn!("📦 Could not save state of job {}: {}", id, e);
+        }
+        if let Some(url) = &meta.callback_url {
+            self.notify(url, &job.status()).await;
+        }
+    }
+    
+    async fn notify(&self, url: &str, status: &JobStatus) {
+        match self.client.post(url).json(status).send().await {
+            Ok(response) if response.status().is_success() => {}
+            Ok(response) => warn!("📦 Callback for job {} returned status {}", status.id, response.status()),
+            Err(e) => warn!("📦 Callback for job {} failed: {}", status.id, e),
+        }
+    }
+    
+    /// Forgets jobs that finished more than `retention_hours` ago and
+    /// deletes their files.
+    async fn sweep(&self) {
+        let cutoff = chrono::Utc::now() - chrono::Duration::hours(self.config.retention_hours as i64);
+        let expired: Vec<String> = {
+            let mut jobs = self.jobs.write().unwrap();
+            let expired: Vec<String> = jobs.iter()
+                .filter(|(_, job)| job.meta.lock().unwrap().finished_at.is_some_and(|at| at < cutoff))
+                .map(|(id, _)| id.clone())
+                .collect();
+            for id in &expired {
+                jobs.remove(id);
+            }
+            expired
+        };
+        
+        for id in &expired {
+            for path in [self.dir.join(format!("{}.json", id)), self.queries_path(id), self.results_path(id)] {
+                match tokio::fs::remove_file(&path).await {
+                    Ok(()) => {}
+                    Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                    Err(e) => warn!("📦 Could not delete {}: {}", path.display(), e),
+                }
+            }
+        }
+        if !expired.is_empty() {
+            info!("🧹 Deleted {} batch jobs older than {}h", expired.len(), self.config.retention_hours);
+        }
+    }
+    
+    /// Writes the job state through a temp file so a crash never leaves it
+    /// half written.
+    async fn save(&self, meta: &JobMeta) -> Result<()> {
+        let path = self.dir.join(format!("{}.json", meta.id));
+        let tmp = self.dir.join(format!("{}.json.tmp", meta.id));
+        let body = serde_json::to_vec_pretty(meta).map_err(internal)?;
+        tokio::fs::write(&tmp, body).await.map_err(internal)?;
+        tokio::fs::rename(&tmp, &path).await.map_err(internal)
+    }
+    
+    fn queries_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.queries.jsonl", id))
+    }
+    
+    fn results_path(&self, id: &str) -> PathBuf {
+        self.dir.join(format!("{}.results.jsonl", id))
+    }
+}
+
+/// Runs queued jobs one after another.
+pub fn spawn(manager: Arc<JobManager>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            let next = manager.queue.lock().unwrap().pop_front();
+            let Some(id) = next else {
+                manager.wake.notified().await;
+                continue;
+            };
+            if let Err(e) = manager.run(&id).await {
+                manager.fail(&id, &e).await;
+            }
+        }
+    })
+}
+
+/// Appends one result line and counts it once it is on disk. A line that
+/// cannot be written fails the job rather than leave a gap in its results.
+async fn record(results: &tokio::sync::Mutex<tokio::fs::File>, job: &Job, result: JobResult) -> Result<()> {
+    let counter = if result.error.is_some() { &job.failed } else { &job.succeeded };
+    let mut line = serde_json::to_string(&result).map_err(internal)?;
+    line.push('\n');
+    let mut file = results.lock().await;
+    async { file.write_all(line.as_bytes()).await?; file.flush().await }
+        .await
+        .map_err(|e| internal(format!("result {}: {}", result.index, e)))?;
+    counter.fetch_add(1, Ordering::Relaxed);
+    Ok(())
+}
+
+/// Deletes expired jobs at startup and then every hour.
+pub fn spawn_sweeper(manager: Arc<JobManager>) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(Duration::from_secs(3600));
+        loop {
+            ticker.tick().await;
+            manager.sweep().await;
+        }
+    })
+}
+
+/// Runs one query, retrying while providers are overloaded or failing.
+async fn run_query(
+    orchestrator: &Orchestrator,
+    config: &JobsConfig,
+    index: usize,
+    query: InferRequest,
+) -> JobResult {
+    let request_id = query.request_id.clone();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match orchestrator.infer(query.clone()).await {
+            Ok(response) => {
+                return JobResult {
+                    index,
+                    request_id,
+                    content: Some(response.content),
+                    winner_model: Some(response.winner_model),
+                    strategy_used: Some(response.strategy_used),
+                    from_cache: response.from_cache,
+                    duration_ms: response.duration_ms,
+                    queue_ms: response.queue_ms,
+                    attempts,
+                    error: None,
+                };
+            }
+            Err(e) if attempts < config.max_attempts && is_retryable(&e) => {
+                tokio::time::sleep(Duration::from_millis(config.retry_backoff_ms * attempts as u64)).await;
+            }
+            Err(e) => {
+                return JobResult {
+                    index,
+                    request_id,
+                    attempts,
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                };
+            }
+        }
+    }
+}
+
+/// Failures that may pass with time: timeouts, busy or unreachable
+/// providers, open breakers and rate limits. Bad queries, schema rejections
+/// and provider errors would fail the same way again.
+fn is_retryable(err: &LLMPoolError) -> bool {
+    matches!(
+        err,
+        LLMPoolError::DeadlineExceeded(_)
+            | LLMPoolError::Overloaded(_)
+            | LLMPoolError::ProviderUnavailable(_)
+            | LLMPoolError::CircuitBreakerOpen(_)
+            | LLMPoolError::RateLimitExceeded
+    )
+}
+
+/// Parsed lines of a results file. A line cut short by a crash is skipped,
+/// so its query runs again.
+fn read_results(path: &Path) -> Vec<JobResult> {
+    std::fs::read_to_string(path)
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect()
+}
+
+fn internal(e: impl std::fmt::Display) -> LLMPoolError {
+    LLMPoolError::Internal(format!("Batch job storage: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cache::Cache;
+    use crate::validation::SchemaRegistry;
+    
+    /// A manager over a fresh jobs dir and a pool with no providers.
+    async fn manager() -> (Arc<JobManager>, PathBuf) {
+        let dir = std::env::temp_dir().join(format!("llm-pool-jobs-{}", uuid::Uuid::new_v4()));
+        let mut config: Config = toml::from_str("providers = []\n[server]\n[qos]\n[ensemble]\n[breaker]\n[cache]\n").unwrap();
+        config.jobs.dir = dir.display().to_string();
+        let config = Arc::new(config);
+        
+        let providers = crate::providers::init(&config).await.unwrap();
+        let cache = Arc::new(Cache::from_config(&config.cache, 10));
+        let schemas = SchemaRegistry::from_config(&config.validation, &config.tasks).unwrap();
+        let orchestrator = Arc::new(Orchestrator::new(config.clone(), providers, cache, None, schemas));
+        (Arc::new(JobManager::open(&config, orchestrator).unwrap()), dir)
+    }
+    
+    #[tokio::test]
+    async fn a_result_that_cannot_be_written_is_an_error() {
+        let (manager, dir) = manager().await;
+        let path = dir.join("read-only.jsonl");
+        std::fs::write(&path, "").unwrap();
+        let file = tokio::sync::Mutex::new(tokio::fs::File::open(&path).await.unwrap());
+        let job = Job {
+            meta: Mutex::new(JobMeta {
+                id: "j".to_string(),
+                state: JobState::Running,
+                total: 1,
+                created_at: chrono::Utc::now(),
+                finished_at: None,
+                callback_url: None,
+                error: None,
+            }),
+            succeeded: AtomicUsize::new(0),
+            failed: AtomicUsize::new(0),
+        };
+        
+        assert!(record(&file, &job, JobResult::default()).await.is_err());
+        assert_eq!(job.status().pending, 1, "nothing counted");
+        drop(manager);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+    
+    #[tokio::test]
+    async fn jobs_with_an_unwritable_results_path_fail() {
+        let (manager, dir) = manager().await;
+        let query: InferRequest = serde_json::from_value(serde_json::json!({
+            "request_id": "r1", "tenant_id": "", "project_id": "", "task": "t", "prompt": "hi",
+            "messages": [], "input": null, "output_schema": null, "max_tokens": 0, "deadline_ms": 0,
+            "sampling": {}, "strategy": null, "images": [], "priority": null,
+        }))
+        .unwrap();
+        let id = manager.submit(vec![query], None, None).await.unwrap().id;
+        std::fs::create_dir(manager.results_path(&id)).unwrap();
+        
+        let running = spawn(manager.clone());
+        let status = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let status = manager.status(&id).unwrap();
+                if status.state != JobState::Queued && status.state != JobState::Running {
+                    return status;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .unwrap();
+        running.abort();
+        
+        assert_eq!(status.state, JobState::Failed);
+        assert!(status.error.unwrap().contains("Batch job storage"));
+        let saved: JobMeta = serde_json::from_slice(&std::fs::read(dir.join(format!("{}.json", id))).unwrap()).unwrap();
+        assert_eq!(saved.state, JobState::Failed);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
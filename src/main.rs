@@ -1,13 +1,20 @@
 mod config;
 mod errors;
+mod idempotency;
+mod jobs;
+mod metrics;
 mod orchestrator;
 mod providers;
 mod server;
+mod shadow;
 mod ensemble;
 mod qos;
 mod security;
+mod singleflight;
 mod cache;
 mod telemetry;
+mod templates;
+mod validation;
 
 use anyhow::Result;
 use std::sync::Arc;
@@ -31,17 +38,64 @@ async fn main() -> Result<()> {
     let providers = providers::init(&config).await?;
     info!("✅ Providers initialized: {:?}", providers.names());
 
+    // Load models in the background; /health reports ready once this is done
+    let warmup_handle = config.warmup.enabled
+        .then(|| providers::warmup::spawn(providers.clone(), &config.warmup));
+    let residency_handle = config.residency.enabled
+        .then(|| providers::residency::spawn(providers.clone(), &config.residency));
+    let health_handle = (config.health.interval_ms > 0)
+        .then(|| providers::health::spawn(providers.clone(), &config.health));
+
+    // Both servers share one cache so a gRPC answer is a hit over HTTP too
+    let cache = Arc::new(cache::Cache::from_config(&config.cache, 10000));
+    info!("✅ Cache initialized (driver: {})", config.cache.driver);
+
+    // Load prompt templates and keep them in sync with prompts/
+    let mut templates_handle = None;
+    let templates = if config.templates.enabled {
+        let registry = Arc::new(templates::TemplateRegistry::load(&config.templates, &config.tasks)?);
+        templates_handle = Some(templates::watch(registry.clone())?);
+        Some(registry)
+    } else {
+        None
+    };
+
+    let schemas = validation::SchemaRegistry::from_config(&config.validation, &config.tasks)?;
+
+    let orchestrator = Arc::new(orchestrator::Orchestrator::new(
+        config.clone(),
+        providers.clone(),
+        cache,
+        templates,
+        schemas,
+    ));
+
+    // Batch jobs left unfinished by the last run resume here
+    let mut jobs_handle = None;
+    let mut sweeper_handle = None;
+    let jobs = if config.jobs.enabled {
+        let manager = Arc::new(jobs::JobManager::open(&config, orchestrator.clone())?);
+        jobs_handle = Some(jobs::spawn(manager.clone()));
+        sweeper_handle = (config.jobs.retention_hours > 0)
+            .then(|| jobs::spawn_sweeper(manager.clone()));
+        Some(manager)
+    } else {
+        None
+    };
+
     // Start servers
     let grpc_config = config.clone();
+    let grpc_orchestrator = orchestrator.clone();
     let grpc_providers = providers.clone();
     let grpc_handle = tokio::spawn(async move {
-        let _ = server::grpc::serve(grpc_config, grpc_providers).await;
+        let _ = server::grpc::serve(grpc_config, grpc_orchestrator, grpc_providers).await;
     });
-    
+
     let http_config = config.clone();
+    let http_orchestrator = orchestrator.clone();
     let http_providers = providers.clone();
     let http_handle = tokio::spawn(async move {
-        let _ = server::http::serve(http_config, http_providers).await;
+        let _ = server::http::serve(http_config, http_orchestrator, http_providers, jobs).await;
     });
 
     info!("✅ gRPC server listening on {}", config.server.grpc_addr);
@@ -57,6 +111,24 @@ async fn main() -> Result<()> {
     }
 
     config_handle.abort();
+    if let Some(handle) = warmup_handle {
+        handle.abort();
+    }
+    if let Some(handle) = residency_handle {
+        handle.abort();
+    }
+    if let Some(handle) = health_handle {
+        handle.abort();
+    }
+    if let Some(handle) = jobs_handle {
+        handle.abort();
+    }
+    if let Some(handle) = sweeper_handle {
+        handle.abort();
+    }
+    if let Some(handle) = templates_handle {
+        handle.abort();
+    }
     info!("👋 LLM Pool Service stopped");
     Ok(())
 }
@@ -0,0 +1,206 @@
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, IntGaugeVec, Opts, Registry, TextEncoder,
+};
+use std::sync::OnceLock;
+
+/// Process-wide Prometheus collectors, exposed on `GET /metrics`.
+pub struct Metrics {
+    registry: Registry,
+    pub requests_coalesced: IntCounterVec,
+    pub requests_shed: IntCounterVec,
+    pub queue_wait: HistogramVec,
+    pub tenant_queue_depth: IntGaugeVec,
+    pub tenant_queue_wait: HistogramVec,
+    pub shadow_requests: IntCounterVec,
+    pub shadow_latency: HistogramVec,
+    pub shadow_similarity: HistogramVec,
+    pub shadow_agreement: IntCounterVec,
+    pub provider_latency_ewma: GaugeVec,
+    pub provider_latency_p95: GaugeVec,
+    pub provider_in_flight: IntGaugeVec,
+    pub endpoint_requests: IntCounterVec,
+    pub endpoint_breaker_open: IntGaugeVec,
+}
+
+impl Metrics {
+    fn new() -> Self {
+        let registry = Registry::new();
+        
+        let requests_coalesced = IntCounterVec::new(
+            Opts::new(
+                "llmpool_requests_coalesced_total",
+                "Requests that shared an identical in-flight ensemble run",
+            ),
+            &["task"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(requests_coalesced.clone())).expect("unique metric");
+        
+        let requests_shed = IntCounterVec::new(
+            Opts::new(
+                "llmpool_requests_shed_total",
+                "Provider calls rejected because no concurrency slot was free in time",
+            ),
+            &["provider", "priority", "tenant"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(requests_shed.clone())).expect("unique metric");
+        
+        let queue_wait = HistogramVec::new(
+            HistogramOpts::new(
+                "llmpool_provider_queue_wait_seconds",
+                "Time provider calls waited for a concurrency slot",
+            )
+            .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["provider", "priority"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(queue_wait.clone())).expect("unique metric");
+        
+        let tenant_queue_depth = IntGaugeVec::new(
+            Opts::new(
+                "llmpool_tenant_queue_depth",
+                "Provider calls currently queued for a slot, per tenant",
+            ),
+            &["tenant"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(tenant_queue_depth.clone())).expect("unique metric");
+        
+        let tenant_queue_wait = HistogramVec::new(
+            HistogramOpts::new(
+                "llmpool_tenant_queue_wait_seconds",
+                "Time a tenant's provider calls waited for a slot",
+            )
+            .buckets(vec![0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5]),
+            &["tenant"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(tenant_queue_wait.clone())).expect("unique metric");
+        
+        let shadow_requests = IntCounterVec::new(
+            Opts::new(
+                "llmpool_shadow_requests_total",
+                "Copies of live requests sent to shadow providers, by outcome",
+            ),
+            &["provider", "task", "outcome"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(shadow_requests.clone())).expect("unique metric");
+        
+        let shadow_latency = HistogramVec::new(
+            HistogramOpts::new(
+                "llmpool_shadow_latency_seconds",
+                "Time shadow providers took to answer mirrored requests",
+            )
+            .buckets(vec![0.05, 0.1, 0.25, 0.5, 1.0, 1.5, 2.5, 5.0, 10.0]),
+            &["provider", "task"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(shadow_latency.clone())).expect("unique metric");
+        
+        let shadow_similarity = HistogramVec::new(
+            HistogramOpts::new(
+                "llmpool_shadow_similarity",
+                "Similarity of shadow answers to the served answer (1 = identical)",
+            )
+            .buckets(vec![0.1, 0.2, 0.3, 0.4, 0.5, 0.6, 0.7, 0.8, 0.9, 1.0]),
+            &["provider", "task"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(shadow_similarity.clone())).expect("unique metric");
+        
+        let shadow_agreement = IntCounterVec::new(
+            Opts::new(
+                "llmpool_shadow_agreement_total",
+                "Shadow answers that agreed or disagreed with the served answer",
+            ),
+            &["provider", "task", "agreed"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(shadow_agreement.clone())).expect("unique metric");
+        
+        let provider_latency_ewma = GaugeVec::new(
+            Opts::new(
+                "llmpool_provider_latency_ewma_seconds",
+                "Moving average of provider inference time, per task",
+            ),
+            &["provider", "task"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(provider_latency_ewma.clone())).expect("unique metric");
+        
+        let provider_latency_p95 = GaugeVec::new(
+            Opts::new(
+                "llmpool_provider_latency_p95_seconds",
+                "95th percentile of recent provider inference times, per task",
+            ),
+            &["provider", "task"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(provider_latency_p95.clone())).expect("unique metric");
+        
+        let provider_in_flight = IntGaugeVec::new(
+            Opts::new(
+                "llmpool_provider_in_flight",
+                "Generation calls running or queued at each provider",
+            ),
+            &["provider"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(provider_in_flight.clone())).expect("unique metric");
+        
+        let endpoint_requests = IntCounterVec::new(
+            Opts::new(
+                "llmpool_endpoint_requests_total",
+                "Calls to each endpoint of multi-endpoint providers, by outcome",
+            ),
+            &["provider", "endpoint", "outcome"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(endpoint_requests.clone())).expect("unique metric");
+        
+        let endpoint_breaker_open = IntGaugeVec::new(
+            Opts::new(
+                "llmpool_endpoint_breaker_open",
+                "1 while an endpoint's circuit breaker is open",
+            ),
+            &["provider", "endpoint"],
+        )
+        .expect("valid metric");
+        registry.register(Box::new(endpoint_breaker_open.clone())).expect("unique metric");
+        
+        Self {
+            registry,
+            requests_coalesced,
+            requests_shed,
+            queue_wait,
+            tenant_queue_depth,
+            tenant_queue_wait,
+            shadow_requests,
+            shadow_latency,
+            shadow_similarity,
+            shadow_agreement,
+            provider_latency_ewma,
+            provider_latency_p95,
+            provider_in_flight,
+            endpoint_requests,
+            endpoint_breaker_open,
+        }
+    }
+    
+    /// Renders every collector in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buf = Vec::new();
+        let encoder = TextEncoder::new();
+        if let Err(e) = encoder.encode(&self.registry.gather(), &mut buf) {
+            tracing::error!("Failed to encode metrics: {}", e);
+        }
+        String::from_utf8(buf).unwrap_or_default()
+    }
+}
+
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(Metrics::new)
+}
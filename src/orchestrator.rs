@@ -1,30 +1,103 @@
-use crate::cache::Cache;
-use crate::config::Config;
-use crate::ensemble::{Ensemble, Strategy};
+use crate::cache::{Cache, CacheFilter, CachedResponse, EmbeddingCache, SemanticCache, TaskStats};
+use crate::config::{Config, Priority, Sampling, TaskConfig};
+use crate::ensemble::{Ensemble, OutputCheck, Strategy};
 use crate::errors::{LLMPoolError, Result};
-use crate::providers::ProviderPool;
+use crate::idempotency::{Claim, IdempotencyStore};
+use crate::metrics::metrics;
+use crate::providers::{flatten_messages, ChatMessage, InferParams, PromptInput, Provider, ProviderPool, CHAT_ROLES};
+use crate::shadow::{Mirrored, ShadowTraffic};
+use crate::singleflight::SingleFlight;
+use crate::templates::{RenderedPrompt, TemplateRegistry};
+use crate::validation::{OutputSchema, SchemaRegistry, ValidationReport};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::sync::Arc;
-use tracing::info;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
 
 pub struct Orchestrator {
     config: Arc<Config>,
     providers: Arc<ProviderPool>,
     ensemble: Ensemble,
     cache: Arc<Cache>,
+    semantic: Option<SemanticCache>,
+    idempotency: Option<IdempotencyStore>,
+    flights: SingleFlight<InferResponse>,
+    templates: Option<Arc<TemplateRegistry>>,
+    schemas: SchemaRegistry,
+    embeddings: EmbeddingCache,
+    shadow: Option<ShadowTraffic>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InferRequest {
     pub request_id: String,
-    #[allow(dead_code)]
     pub tenant_id: String,
-    #[allow(dead_code)]
     pub project_id: String,
     pub task: String,
     pub prompt: String,
+    /// Role-tagged conversation, as an alternative to `prompt`
+    pub messages: Vec<ChatMessage>,
+    /// Structured JSON input to render through the task template
+    pub input: Option<String>,
+    /// JSON Schema for this request's output, overriding the task schema
+    pub output_schema: Option<String>,
+    /// 0 takes the task default
     pub max_tokens: i32,
+    /// 0 takes the task default
     pub deadline_ms: i32,
+    /// Sampling overrides; unset fields take the task and provider defaults
+    pub sampling: Sampling,
     pub strategy: Option<String>,
+    /// Base64-encoded images for vision models
+    pub images: Vec<String>,
+    /// Queue class; unset takes the tenant's default
+    pub priority: Option<Priority>,
+}
+
+impl InferRequest {
+    /// The prompt, or the flattened messages of a chat request. Size limits
+    /// and cache lookups work on this text.
+    pub fn text(&self) -> Cow<'_, str> {
+        if self.messages.is_empty() {
+            Cow::Borrowed(&self.prompt)
+        } else {
+            Cow::Owned(flatten_messages(&self.messages))
+        }
+    }
+    
+    fn prompt_input(&self) -> PromptInput {
+        if self.messages.is_empty() {
+            PromptInput::Text(self.prompt.clone())
+        } else {
+            PromptInput::Messages(self.messages.clone())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbedRequest {
+    pub request_id: String,
+    pub tenant_id: String,
+    pub project_id: String,
+    pub inputs: Vec<String>,
+    /// Provider to use; any provider with the `embed` capability when unset
+    pub provider: Option<String>,
+    /// 0 takes the QoS default
+    pub deadline_ms: i32,
+}
+
+#[derive(Debug, Clone)]
+pub struct EmbedResponse {
+    pub request_id: String,
+    pub provider: String,
+    /// One vector per input, in order
+    pub embeddings: Vec<Vec<f32>>,
+    /// How many of the vectors came from the cache
+    pub cached: usize,
+    pub duration_ms: i32,
 }
 
 #[derive(Debug, Clone)]
@@ -33,9 +106,14 @@ pub struct InferResponse {
     pub content: String,
     pub winner_model: String,
     pub duration_ms: i32,
+    /// Time the winning call waited for a provider slot
+    pub queue_ms: i32,
     pub from_cache: bool,
     pub strategy_used: String,
     pub models_queried: Vec<String>,
+    /// Output schema check, for fresh answers to tasks with a schema
+    pub validation: Option<ValidationReport>,
+    pub meta: HashMap<String, String>,
 }
 
 impl Orchestrator {
@@ -43,84 +121,582 @@ impl Orchestrator {
         config: Arc<Config>,
         providers: Arc<ProviderPool>,
         cache: Arc<Cache>,
+        templates: Option<Arc<TemplateRegistry>>,
+        schemas: SchemaRegistry,
     ) -> Self {
         let ensemble = Ensemble::new(config.clone());
+        let semantic = SemanticCache::from_config(
+            &config.cache.semantic,
+            &providers,
+            config.cache.ttl_seconds,
+        );
+        let idempotency = config.idempotency.enabled
+            .then(|| IdempotencyStore::new(&config.idempotency));
+        let embeddings = EmbeddingCache::new(
+            config.embeddings.cache_ttl_seconds,
+            config.embeddings.cache_max_entries,
+        );
+        let shadow = ShadowTraffic::from_config(&config, &providers);
         Self {
             config,
             providers,
             ensemble,
             cache,
+            semantic,
+            idempotency,
+            flights: SingleFlight::new(),
+            templates,
+            schemas,
+            embeddings,
+            shadow,
         }
     }
     
     pub async fn infer(&self, req: InferRequest) -> Result<InferResponse> {
+        let Some(store) = self.idempotency.as_ref().filter(|_| !req.request_id.is_empty()) else {
+            return self.execute(req).await;
+        };
+        
+        match store.claim(&req).await? {
+            Claim::Replay(mut response) => {
+                info!("🔁 Replaying answer for duplicate request: {}", req.request_id);
+                response.meta.insert("idempotent_replay".to_string(), "true".to_string());
+                Ok(*response)
+            }
+            Claim::Owner(guard) => {
+                let result = self.execute(req).await;
+                if let Ok(response) = &result {
+                    guard.complete(response);
+                }
+                result
+            }
+        }
+    }
+    
+    async fn execute(&self, mut req: InferRequest) -> Result<InferResponse> {
+        self.check_task(&req.task)?;
+        self.apply_task_defaults(&mut req);
+        let rendered = self.render_input(&mut req)?;
+        let mut response = self.resolve(req).await?;
+        
+        if let Some(rendered) = rendered {
+            response.meta.insert("template".to_string(), rendered.template);
+            response.meta.insert("template_version".to_string(), rendered.version);
+        }
+        Ok(response)
+    }
+    
+    /// Builds the prompt from the task template when the request carries
+    /// structured input instead of a prompt.
+    fn render_input(&self, req: &mut InferRequest) -> Result<Option<RenderedPrompt>> {
+        let Some(input) = req.input.as_deref() else {
+            return Ok(None);
+        };
+        if !req.prompt.is_empty() || !req.messages.is_empty() {
+            return Err(LLMPoolError::InvalidQuery(
+                "Set only one of prompt, messages or input".to_string()
+            ));
+        }
+        let Some(templates) = &self.templates else {
+            return Err(LLMPoolError::InvalidQuery(
+                "Prompt templates are disabled; send a prompt instead".to_string()
+            ));
+        };
+        
+        let value: serde_json::Value = serde_json::from_str(input)
+            .map_err(|e| LLMPoolError::InvalidQuery(format!("input is not valid JSON: {}", e)))?;
+        let pretty = serde_json::to_string_pretty(&value).unwrap_or_else(|_| input.to_string());
+        
+        let rendered = templates.render(&req.task, &pretty)?;
+        req.prompt = rendered.prompt.clone();
+        Ok(Some(rendered))
+    }
+    
+    async fn resolve(&self, mut req: InferRequest) -> Result<InferResponse> {
         info!("🎯 Orchestrating request: {} (task: {})", req.request_id, req.task);
         
         // Validate request
         self.validate(&req)?;
+        let schema = self.output_schema(&req)?;
+        let key = self.cache_key(&req);
         
         // Check cache
         if self.config.cache.enabled {
-            if let Some(cached) = self.cache.get(&req.task, &req.prompt, req.max_tokens).await {
+            if let Some(cached) = self.cache.get(&req.task, &key).await {
                 info!("💾 Cache hit for request: {}", req.request_id);
                 return Ok(InferResponse {
                     request_id: req.request_id,
                     content: cached.content,
                     winner_model: cached.model,
                     duration_ms: 0,
+                    queue_ms: 0,
+                    from_cache: true,
+                    strategy_used: "CACHE".to_string(),
+                    models_queried: vec![],
+                    validation: None,
+                    meta: HashMap::new(),
+                });
+            }
+        }
+        
+        // Near-identical prompts can still reuse an answer via the semantic tier
+        let mut embedding = None;
+        if let Some(semantic) = self.semantic.as_ref().filter(|s| self.config.cache.enabled && s.covers(&req.task)) {
+            let started = Instant::now();
+            let params = self.embed_params(&req.tenant_id, self.priority(&req), 0);
+            embedding = semantic.embed(&req.text(), &params).await;
+            let hit = embedding.as_deref()
+                .and_then(|v| semantic.lookup(&req.task, &self.scope(&req), v));
+            if let Some((cached, similarity)) = hit {
+                info!("🧭 Semantic cache hit for request: {} (similarity {:.4})", req.request_id, similarity);
+                let mut meta = HashMap::new();
+                meta.insert("semantic_cache".to_string(), "hit".to_string());
+                meta.insert("similarity".to_string(), format!("{:.4}", similarity));
+                return Ok(InferResponse {
+                    request_id: req.request_id,
+                    content: cached.content,
+                    winner_model: cached.model,
+                    duration_ms: 0,
+                    queue_ms: 0,
                     from_cache: true,
                     strategy_used: "CACHE".to_string(),
                     models_queried: vec![],
+                    validation: None,
+                    meta,
                 });
             }
+            
+            // The embed ran on the request's clock, so the ensemble only
+            // gets what is left of the deadline
+            if req.deadline_ms > 0 {
+                let left_ms = req.deadline_ms - started.elapsed().as_millis() as i32;
+                if left_ms <= 0 {
+                    return Err(LLMPoolError::DeadlineExceeded(req.deadline_ms));
+                }
+                req.deadline_ms = left_ms;
+            }
+        }
+        
+        // Identical requests already in flight share one ensemble run. Only
+        // requests of the same tenant and class share, since the leader's
+        // queue position and shedding apply to everyone waiting on it, and
+        // nobody waits past their own deadline.
+        let result = if self.config.qos.coalesce_inflight {
+            let work = self.run_ensemble(&req, embedding, schema);
+            let flight = format!("{}:{}:{}", key, self.priority(&req).as_str(), req.tenant_id);
+            let patience = Duration::from_millis(req.deadline_ms.max(0) as u64);
+            let (result, coalesced) = self.flights.run(flight, work, patience).await;
+            if coalesced {
+                info!("🔗 Coalesced request {} onto an in-flight twin", req.request_id);
+                metrics().requests_coalesced.with_label_values(&[&req.task]).inc();
+            }
+            result.map(|mut response| {
+                if coalesced {
+                    response.request_id = req.request_id.clone();
+                    response.meta.insert("coalesced".to_string(), "true".to_string());
+                }
+                response
+            })
+        } else {
+            self.run_ensemble(&req, embedding, schema).await
+        };
+        
+        match result {
+            Err(e) if self.config.cache.enabled && is_provider_failure(&e) => {
+                self.serve_stale(&req, &key, e).await
+            }
+            other => other,
         }
+    }
+    
+    /// Falls back to an expired answer when providers cannot produce a new
+    /// one; returns the original error if nothing is left in the cache.
+    async fn serve_stale(&self, req: &InferRequest, key: &str, err: LLMPoolError) -> Result<InferResponse> {
+        let Some(stale) = self.cache.get_stale(key).await else {
+            return Err(err);
+        };
         
+        warn!("🥀 Serving stale answer for request {} ({}s old) after: {}",
+            req.request_id, stale.age_secs(), err);
+        let mut meta = HashMap::new();
+        meta.insert("stale".to_string(), "true".to_string());
+        meta.insert("stale_age_seconds".to_string(), stale.age_secs().to_string());
+        meta.insert("stale_reason".to_string(), err.to_string());
+        Ok(InferResponse {
+            request_id: req.request_id.clone(),
+            content: stale.content,
+            winner_model: stale.model,
+            duration_ms: 0,
+            queue_ms: 0,
+            from_cache: true,
+            strategy_used: "CACHE".to_string(),
+            models_queried: vec![],
+            validation: None,
+            meta,
+        })
+    }
+    
+    async fn run_ensemble(
+        &self,
+        req: &InferRequest,
+        embedding: Option<Vec<f32>>,
+        schema: Option<Arc<OutputSchema>>,
+    ) -> Result<InferResponse> {
         // Get providers for this task
-        let providers = self.providers.providers_for_task(&req.task);
+        let mut providers = self.providers.providers_for_task(&req.task);
         if providers.is_empty() {
             return Err(LLMPoolError::EnsembleError(
                 format!("No providers available for task: {}", req.task)
             ));
         }
         
+        // Images can only go to models that can see them
+        if !req.images.is_empty() {
+            providers.retain(|p| self.providers.has_capability(p.name(), "vision"));
+            if providers.is_empty() {
+                return Err(LLMPoolError::InvalidQuery(
+                    format!("No vision-capable provider for task: {}", req.task)
+                ));
+            }
+        }
+        
         // Determine strategy
-        let strategy_name = req.strategy
+        let strategy_name = req.strategy.clone()
             .or_else(|| self.config.ensemble.strategy_by_task.get(&req.task).cloned())
             .unwrap_or_else(|| self.config.ensemble.default_strategy.clone());
         
         let strategy = Strategy::from_str(&strategy_name);
         
+        // FASTEST takes the first answer, so try the quickest provider
+        // first; placement below keeps loaded models ahead of it
+        let providers = match strategy {
+            Strategy::Fastest => self.providers.by_latency(providers, &req.task),
+            _ => providers,
+        };
+        
+        // Prefer models already loaded and keep hosts under their model cap
+        let providers = self.providers.place(providers);
+        
+        let params = InferParams {
+            max_tokens: req.max_tokens,
+            deadline_ms: req.deadline_ms,
+            sampling: match self.task_config(&req.task) {
+                Some(task) => req.sampling.or(&task.sampling),
+                None => req.sampling.clone(),
+            },
+            output_schema: schema.as_ref().map(|s| s.value()),
+            images: req.images.as_slice().into(),
+            priority: self.priority(req),
+            tenant_id: req.tenant_id.clone(),
+            tenant_weight: self.config.tenancy.get(&req.tenant_id).map_or(1.0, |t| t.weight),
+            task: req.task.clone(),
+        };
+        let check = schema.map(|schema| OutputCheck {
+            schema,
+            reask: self.config.validation.reask,
+        });
+        
         // Execute ensemble
+        let input = req.prompt_input();
         let result = self.ensemble.execute(
             strategy,
             providers,
-            &req.prompt,
-            req.max_tokens,
-            req.deadline_ms,
+            &input,
+            &params,
+            check,
         ).await?;
         
-        // Cache the result
-        if self.config.cache.enabled {
-            self.cache.set(
-                &req.task,
-                &req.prompt,
-                req.max_tokens,
-                &result.response.content,
-                &result.response.model,
-            ).await;
+        // Candidate models see the same request; their answers are only logged
+        if let Some(shadow) = &self.shadow {
+            shadow.mirror(Mirrored {
+                request_id: &req.request_id,
+                task: &req.task,
+                tenant_id: &req.tenant_id,
+                input: &input,
+                params: &params,
+                winner: &result.response,
+            });
         }
         
+        // Cache the result
+        let cached = CachedResponse::new(
+            &req.task,
+            &req.tenant_id,
+            &result.response.content,
+            &result.response.model,
+        );
+        self.store_answer(req, cached, embedding).await;
+        
         Ok(InferResponse {
-            request_id: req.request_id,
+            request_id: req.request_id.clone(),
             content: result.response.content,
             winner_model: result.response.model,
             duration_ms: result.response.duration_ms,
+            queue_ms: result.response.queue_ms,
             from_cache: false,
             strategy_used: strategy_name,
             models_queried: result.models_queried,
+            validation: result.validation,
+            meta: HashMap::new(),
         })
     }
     
+    async fn store_answer(&self, req: &InferRequest, mut cached: CachedResponse, embedding: Option<Vec<f32>>) {
+        if !self.config.cache.enabled {
+            return;
+        }
+        cached.ttl_seconds = self.task_config(&req.task).and_then(|t| t.cache_ttl_seconds);
+        
+        let key = self.cache_key(req);
+        if let (Some(semantic), Some(vector)) = (&self.semantic, embedding) {
+            semantic.insert(key.clone(), self.scope(req), vector, cached.clone());
+        }
+        self.cache.set(&key, cached).await;
+    }
+    
+    /// The request's own output schema if it sent one, else the task's.
+    fn output_schema(&self, req: &InferRequest) -> Result<Option<Arc<OutputSchema>>> {
+        let Some(raw) = req.output_schema.as_deref() else {
+            return Ok(self.schemas.get(&req.task));
+        };
+        let value: serde_json::Value = serde_json::from_str(raw)
+            .map_err(|e| LLMPoolError::InvalidQuery(format!("output_schema is not valid JSON: {}", e)))?;
+        let schema = OutputSchema::compile(&value)
+            .map_err(|e| LLMPoolError::InvalidQuery(format!("output_schema: {}", e)))?;
+        Ok(Some(Arc::new(schema)))
+    }
+    
+    fn cache_key(&self, req: &InferRequest) -> String {
+        self.cache.make_key(&req.task, &req.text(), req.max_tokens, &variant(req))
+    }
+    
+    /// Semantic-cache scope: answers are only reused across requests that
+    /// agree on everything but the prompt text.
+    fn scope(&self, req: &InferRequest) -> String {
+        format!("{}:{}", req.max_tokens, variant(req))
+    }
+    
+    /// Stores a known answer without running the ensemble, for cache warming.
+    pub async fn warm_cache(&self, mut req: InferRequest, content: &str, model: &str) -> Result<()> {
+        self.check_task(&req.task)?;
+        self.apply_task_defaults(&mut req);
+        self.render_input(&mut req)?;
+        self.validate(&req)?;
+        
+        let embedding = match &self.semantic {
+            Some(semantic) if semantic.covers(&req.task) => {
+                let params = self.embed_params(&req.tenant_id, self.priority(&req), 0);
+                semantic.embed(&req.text(), &params).await
+            }
+            _ => None,
+        };
+        let cached = CachedResponse::new(&req.task, &req.tenant_id, content, model);
+        self.store_answer(&req, cached, embedding).await;
+        Ok(())
+    }
+    
+    /// Vectorizes a batch of inputs on the first embedding provider that
+    /// answers, reusing cached vectors for inputs seen before.
+    pub async fn embed(&self, mut req: EmbedRequest) -> Result<EmbedResponse> {
+        info!("🧮 Embedding request: {} ({} inputs, tenant: {}, project: {})",
+            req.request_id, req.inputs.len(), req.tenant_id, req.project_id);
+        
+        if req.deadline_ms == 0 {
+            req.deadline_ms = self.config.qos.deadline_default_ms;
+        }
+        self.validate_embed(&req)?;
+        
+        let providers = match &req.provider {
+            Some(name) if self.providers.has_capability(name, "embed") => {
+                self.providers.get(name).into_iter().collect()
+            }
+            Some(name) => {
+                return Err(LLMPoolError::InvalidQuery(
+                    format!("Provider {} does not declare the embed capability", name)
+                ));
+            }
+            None => self.providers.providers_with("embed"),
+        };
+        if providers.is_empty() {
+            return Err(LLMPoolError::ProviderError(
+                "No provider declares the embed capability".to_string()
+            ));
+        }
+        
+        let start = Instant::now();
+        let mut last_error = None;
+        for provider in providers {
+            let remaining_ms = req.deadline_ms - start.elapsed().as_millis() as i32;
+            if remaining_ms <= 0 {
+                return Err(LLMPoolError::DeadlineExceeded(req.deadline_ms));
+            }
+            
+            // Embed requests carry no priority of their own and queue in
+            // their tenant's default class
+            let priority = self.config.tenancy.get(&req.tenant_id)
+                .map(|t| t.default_priority)
+                .unwrap_or_default();
+            let params = self.embed_params(&req.tenant_id, priority, remaining_ms);
+            match self.embed_with(provider.as_ref(), &req.inputs, &params).await {
+                Ok((embeddings, cached)) => {
+                    return Ok(EmbedResponse {
+                        request_id: req.request_id,
+                        provider: provider.name().to_string(),
+                        embeddings,
+                        cached,
+                        duration_ms: start.elapsed().as_millis() as i32,
+                    });
+                }
+                Err(e) => {
+                    warn!("Embedding provider {} failed: {}", provider.name(), e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        
+        Err(last_error.unwrap_or_else(|| {
+            LLMPoolError::ProviderError("All embedding providers failed".to_string())
+        }))
+    }
+    
+    /// Returns the vectors and how many came from the cache.
+    async fn embed_with(
+        &self,
+        provider: &dyn Provider,
+        inputs: &[String],
+        params: &InferParams,
+    ) -> Result<(Vec<Vec<f32>>, usize)> {
+        let use_cache = self.config.cache.enabled;
+        let mut vectors: Vec<Option<Vec<f32>>> = vec![None; inputs.len()];
+        if use_cache {
+            for (slot, input) in vectors.iter_mut().zip(inputs) {
+                *slot = self.embeddings.get(provider.name(), input).await
+                    .map(|v| v.as_ref().clone());
+            }
+        }
+        
+        let missing: Vec<usize> = (0..inputs.len()).filter(|&i| vectors[i].is_none()).collect();
+        let cached = inputs.len() - missing.len();
+        
+        if !missing.is_empty() {
+            let batch: Vec<String> = missing.iter().map(|&i| inputs[i].clone()).collect();
+            let fresh = tokio::time::timeout(
+                Duration::from_millis(params.deadline_ms as u64),
+                provider.embed(&batch, params),
+            )
+            .await
+            .map_err(|_| LLMPoolError::DeadlineExceeded(params.deadline_ms))??;
+            
+            for (i, vector) in missing.into_iter().zip(fresh) {
+                if use_cache {
+                    self.embeddings.insert(provider.name(), &inputs[i], vector.clone()).await;
+                }
+                vectors[i] = Some(vector);
+            }
+        }
+        
+        // A provider that returns fewer vectors than inputs leaves gaps
+        let vectors = vectors.into_iter()
+            .collect::<Option<Vec<_>>>()
+            .ok_or_else(|| LLMPoolError::ProviderError(
+                format!("{} returned fewer embeddings than inputs", provider.name())
+            ))?;
+        Ok((vectors, cached))
+    }
+    
+    /// Params for an embed call; only the deadline and the queue identity
+    /// at providers with a concurrency limit matter.
+    fn embed_params(&self, tenant_id: &str, priority: Priority, deadline_ms: i32) -> InferParams {
+        InferParams {
+            deadline_ms,
+            priority,
+            tenant_id: tenant_id.to_string(),
+            tenant_weight: self.config.tenancy.get(tenant_id).map_or(1.0, |t| t.weight),
+            ..Default::default()
+        }
+    }
+    
+    fn validate_embed(&self, req: &EmbedRequest) -> Result<()> {
+        if req.inputs.is_empty() {
+            return Err(LLMPoolError::InvalidQuery("inputs must not be empty".to_string()));
+        }
+        if req.inputs.len() > self.config.embeddings.max_inputs {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("{} inputs exceeds max {}", req.inputs.len(), self.config.embeddings.max_inputs)
+            ));
+        }
+        if let Some(input) = req.inputs.iter().find(|i| i.len() > self.config.qos.max_prompt_bytes) {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("Input size {} exceeds max {} bytes", input.len(), self.config.qos.max_prompt_bytes)
+            ));
+        }
+        if req.deadline_ms > self.config.qos.max_deadline_ms {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("Deadline {}ms exceeds max {}ms",
+                    req.deadline_ms, self.config.qos.max_deadline_ms)
+            ));
+        }
+        Ok(())
+    }
+    
+    pub fn cache_stats(&self) -> BTreeMap<String, TaskStats> {
+        self.cache.stats()
+    }
+    
+    /// Drops matching answers from every cache tier and reports how many
+    /// entries each tier lost.
+    pub async fn invalidate_cache(&self, filter: &CacheFilter) -> BTreeMap<&'static str, u64> {
+        let (memory, shared) = self.cache.invalidate(filter).await;
+        let semantic = self.semantic.as_ref().map_or(0, |s| s.invalidate(filter));
+        info!("🧹 Cache invalidated ({:?}): memory={}, shared={}, semantic={}",
+            filter, memory, shared, semantic);
+        
+        BTreeMap::from([("memory", memory), ("shared", shared), ("semantic", semantic)])
+    }
+    
+    fn task_config(&self, task: &str) -> Option<&TaskConfig> {
+        self.config.tasks.get(task)
+    }
+    
+    /// Fills what the request left unset from `[tasks.<name>]`, then from
+    /// the global QoS defaults.
+    fn apply_task_defaults(&self, req: &mut InferRequest) {
+        let task = self.task_config(&req.task);
+        if req.max_tokens == 0 {
+            req.max_tokens = task.and_then(|t| t.max_tokens)
+                .unwrap_or(self.config.qos.max_tokens_default);
+        }
+        if req.deadline_ms == 0 {
+            req.deadline_ms = task.and_then(|t| t.deadline_ms)
+                .unwrap_or(self.config.qos.deadline_default_ms);
+        }
+        if req.strategy.is_none() {
+            req.strategy = task.and_then(|t| t.strategy.clone());
+        }
+    }
+    
+    /// Rejects tasks no provider is configured for, listing the ones that are.
+    fn check_task(&self, task: &str) -> Result<()> {
+        if self.providers.has_task(task) {
+            return Ok(());
+        }
+        let known = self.providers.tasks().join(", ");
+        if task.is_empty() {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("Task is required; configured tasks: {}", known)
+            ));
+        }
+        Err(LLMPoolError::InvalidQuery(
+            format!("Unknown task: {}; configured tasks: {}", task, known)
+        ))
+    }
+    
+    fn priority(&self, req: &InferRequest) -> Priority {
+        req.priority
+            .or_else(|| self.config.tenancy.get(&req.tenant_id).map(|t| t.default_priority))
+            .unwrap_or_default()
+    }
+    
     fn validate(&self, req: &InferRequest) -> Result<()> {
         // Check deadline
         if req.deadline_ms > self.config.qos.max_deadline_ms {
@@ -130,11 +706,32 @@ impl Orchestrator {
             ));
         }
         
+        match (req.prompt.is_empty(), req.messages.is_empty()) {
+            (true, true) => {
+                return Err(LLMPoolError::InvalidQuery(
+                    "One of prompt, messages or input is required".to_string()
+                ));
+            }
+            (false, false) => {
+                return Err(LLMPoolError::InvalidQuery(
+                    "Set either prompt or messages, not both".to_string()
+                ));
+            }
+            _ => {}
+        }
+        
+        if let Some(m) = req.messages.iter().find(|m| !CHAT_ROLES.contains(&m.role.as_str())) {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("Unknown message role: {} (expected one of {})", m.role, CHAT_ROLES.join(", "))
+            ));
+        }
+        
         // Check prompt size
-        if req.prompt.len() > self.config.qos.max_prompt_bytes {
+        let prompt_bytes = req.text().len();
+        if prompt_bytes > self.config.qos.max_prompt_bytes {
             return Err(LLMPoolError::InvalidQuery(
                 format!("Prompt size {} exceeds max {} bytes",
-                    req.prompt.len(), self.config.qos.max_prompt_bytes)
+                    prompt_bytes, self.config.qos.max_prompt_bytes)
             ));
         }
         
@@ -145,6 +742,63 @@ impl Orchestrator {
             ));
         }
         
+        req.sampling.check().map_err(LLMPoolError::InvalidQuery)?;
+        
+        // Images have their own budget, counted after decoding
+        let mut image_bytes = 0;
+        for (i, image) in req.images.iter().enumerate() {
+            let decoded = BASE64.decode(image)
+                .map_err(|e| LLMPoolError::InvalidQuery(format!("Image {} is not valid base64: {}", i, e)))?;
+            image_bytes += decoded.len();
+        }
+        if image_bytes > self.config.qos.max_image_bytes {
+            return Err(LLMPoolError::InvalidQuery(
+                format!("Image size {} exceeds max {} bytes",
+                    image_bytes, self.config.qos.max_image_bytes)
+            ));
+        }
+        
         Ok(())
     }
 }
+
+/// Errors that mean "no provider could answer", as opposed to a bad request.
+pub(crate) fn is_provider_failure(err: &LLMPoolError) -> bool {
+    matches!(
+        err,
+        LLMPoolError::ProviderError(_)
+            | LLMPoolError::ProviderUnavailable(_)
+            | LLMPoolError::EnsembleError(_)
+            | LLMPoolError::DeadlineExceeded(_)
+            | LLMPoolError::CircuitBreakerOpen(_)
+            | LLMPoolError::Overloaded(_)
+    )
+}
+
+/// Digest of request settings other than task, prompt and max_tokens that
+/// change the answer; empty when the request uses none of them.
+fn variant(req: &InferRequest) -> String {
+    use sha2::{Digest, Sha256};
+    if req.output_schema.is_none() && req.sampling.is_empty() && req.messages.is_empty() && req.images.is_empty() {
+        return String::new();
+    }
+    let mut hasher = Sha256::new();
+    // The key already covers the flattened text; this keeps a chat request
+    // apart from a plain prompt that happens to look the same
+    if !req.messages.is_empty() {
+        hasher.update(b"messages\0");
+    }
+    if let Some(schema) = req.output_schema.as_deref() {
+        hasher.update(b"output_schema\0");
+        hasher.update(schema.as_bytes());
+    }
+    if !req.sampling.is_empty() {
+        hasher.update(b"sampling\0");
+        hasher.update(serde_json::to_string(&req.sampling).unwrap_or_default().as_bytes());
+    }
+    for image in &req.images {
+        hasher.update(b"image\0");
+        hasher.update(image.as_bytes());
+    }
+    format!("{:x}", hasher.finalize())
+}
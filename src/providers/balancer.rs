@@ -0,0 +1,306 @@
+use super::{ChatMessage, InferParams, Provider, ProviderResponse};
+use crate::config::{BreakerConfig, ProviderConfig};
+use crate::errors::{LLMPoolError, Result};
+use crate::metrics::metrics;
+use crate::qos::breaker::{CircuitBreaker, Ticket, Transition};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// One provider served by several replicas of the same model.
+///
+/// Each call goes to one endpoint, picked by fewest calls in flight or in
+/// turn, or by a hash of the prompt when `prompt_affinity` is set. Endpoints
+/// keep their own breaker and probe result; open or unhealthy ones are left
+/// out while others remain, and a failed call moves on to the next endpoint.
+/// Ensembles see a single provider and a single model.
+pub struct Balanced {
+    name: String,
+    round_robin: bool,
+    prompt_affinity: bool,
+    endpoints: Vec<Endpoint>,
+    next: AtomicUsize,
+}
+
+struct Endpoint {
+    url: String,
+    provider: Arc<dyn Provider>,
+    breaker: CircuitBreaker,
+    in_flight: AtomicUsize,
+    /// Result of the last health probe
+    healthy: AtomicBool,
+}
+
+/// Counts a call against its endpoint until dropped.
+struct Outstanding<'a>(&'a AtomicUsize);
+
+impl Drop for Outstanding<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+impl Balanced {
+    /// `endpoints` pairs each URL with a driver already pointed at it.
+    pub fn new(config: &ProviderConfig, breaker: &BreakerConfig, endpoints: Vec<(String, Arc<dyn Provider>)>) -> Self {
+        info!("🔀 {} balances {} endpoints ({})", config.name, endpoints.len(), config.balance);
+        Self {
+            name: config.name.clone(),
+            round_robin: config.balance == "round_robin",
+            prompt_affinity: config.prompt_affinity,
+            endpoints: endpoints.into_iter()
+                .map(|(url, provider)| Endpoint {
+                    url,
+                    provider,
+                    breaker: CircuitBreaker::new(breaker),
+                    in_flight: AtomicUsize::new(0),
+                    healthy: AtomicBool::new(true),
+                })
+                .collect(),
+            next: AtomicUsize::new(0),
+        }
+    }
+    
+    /// The driver for one endpoint, e.g. to ask its host what it has loaded.
+    pub fn endpoint(&self, url: &str) -> Option<Arc<dyn Provider>> {
+        self.endpoints.iter()
+            .find(|e| e.url.trim_end_matches('/') == url.trim_end_matches('/'))
+            .map(|e| e.provider.clone())
+    }
+    
+    /// Endpoint URL → "healthy", "unhealthy" or "open" (breaker tripped).
+    pub fn status(&self) -> HashMap<String, String> {
+        self.endpoints.iter()
+            .map(|e| {
+                let status = if e.breaker.is_open() {
+                    "open"
+                } else if e.healthy.load(Ordering::Relaxed) {
+                    "healthy"
+                } else {
+                    "unhealthy"
+                };
+                (e.url.clone(), status.to_string())
+            })
+            .collect()
+    }
+    
+    /// Endpoints in the order to try them. Open breakers are left out;
+    /// endpoints that failed their last probe go last.
+    fn order(&self, key: Option<u64>) -> Vec<&Endpoint> {
+        let mut candidates: Vec<(usize, &Endpoint)> = self.endpoints.iter()
+            .enumerate()
+            .filter(|(_, e)| !e.breaker.is_open())
+            .collect();
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        let turn = |i: usize| (i + self.endpoints.len() - start % self.endpoints.len()) % self.endpoints.len();
+        
+        match key {
+            // Rendezvous hashing: removing an endpoint only moves its own prompts
+            Some(key) => candidates.sort_by_key(|(_, e)| std::cmp::Reverse(score(key, &e.url))),
+            None if self.round_robin => candidates.sort_by_key(|(i, _)| turn(*i)),
+            // Ties go in turn so idle endpoints share the load
+            None => candidates.sort_by_key(|(i, e)| (e.in_flight.load(Ordering::Relaxed), turn(*i))),
+        }
+        candidates.sort_by_key(|(_, e)| !e.healthy.load(Ordering::Relaxed));
+        candidates.into_iter().map(|(_, e)| e).collect()
+    }
+    
+    /// Runs `call` on endpoints in order until one succeeds. With a deadline,
+    /// each attempt gets an equal share of the time left over the endpoints
+    /// not yet tried, so a hung endpoint cannot use up the whole deadline;
+    /// `call` is told its share.
+    async fn dispatch<'a, T, F, Fut>(&'a self, key: Option<u64>, deadline_ms: i32, call: F) -> Result<T>
+    where
+        F: Fn(&'a Arc<dyn Provider>, i32) -> Fut,
+        Fut: Future<Output = Result<T>>,
+    {
+        let start = Instant::now();
+        let endpoints = self.order(key);
+        let mut last_error = None;
+        for (tried, endpoint) in endpoints.iter().copied().enumerate() {
+            let Some(ticket) = endpoint.breaker.allow() else {
+                continue;
+            };
+            let share_ms = if deadline_ms > 0 {
+                let left_ms = deadline_ms - start.elapsed().as_millis() as i32;
+                if left_ms <= 0 {
+                    break;
+                }
+                (left_ms / (endpoints.len() - tried) as i32).max(1)
+            } else {
+                0
+            };
+            
+            endpoint.in_flight.fetch_add(1, Ordering::Relaxed);
+            let outstanding = Outstanding(&endpoint.in_flight);
+            let result = match share_ms {
+                0 => call(&endpoint.provider, deadline_ms).await,
+                ms => tokio::time::timeout(Duration::from_millis(ms as u64), call(&endpoint.provider, ms))
+                    .await
+                    .unwrap_or(Err(LLMPoolError::DeadlineExceeded(ms))),
+            };
+            drop(outstanding);
+            
+            self.record(endpoint, ticket, result.as_ref().err());
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) if is_endpoint_failure(&e) => {
+                    warn!("🔀 {} endpoint {} failed: {}", self.name, endpoint.url, e);
+                    last_error = Some(e);
+                }
+                // Another endpoint would reject the same call the same way
+                Err(e) => return Err(e),
+            }
+        }
+        Err(last_error.unwrap_or_else(|| match deadline_ms {
+            ms if ms > 0 && start.elapsed().as_millis() as i32 >= ms => LLMPoolError::DeadlineExceeded(ms),
+            _ => LLMPoolError::CircuitBreakerOpen(self.name.clone()),
+        }))
+    }
+    
+    /// Counts the call; only failures of the endpoint itself reach its
+    /// breaker, so a burst of bad requests cannot open every replica.
+    fn record(&self, endpoint: &Endpoint, ticket: Ticket, error: Option<&LLMPoolError>) {
+        metrics().endpoint_requests
+            .with_label_values(&[&self.name, &endpoint.url, if error.is_none() { "ok" } else { "error" }])
+            .inc();
+        match endpoint.breaker.record(ticket, !error.is_some_and(is_endpoint_failure)) {
+            Some(Transition::Opened) => {
+                warn!("🔀 {} endpoint {} breaker opened", self.name, endpoint.url);
+                metrics().endpoint_breaker_open.with_label_values(&[&self.name, &endpoint.url]).set(1);
+            }
+            Some(Transition::Closed) => {
+                info!("🔀 {} endpoint {} breaker closed", self.name, endpoint.url);
+                metrics().endpoint_breaker_open.with_label_values(&[&self.name, &endpoint.url]).set(0);
+            }
+            None => {}
+        }
+    }
+    
+    fn affinity<T: Hash + ?Sized>(&self, value: &T) -> Option<u64> {
+        self.prompt_affinity.then(|| {
+            let mut hasher = std::collections::hash_map::DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        })
+    }
+}
+
+/// Unreachable or too slow; anything else means the endpoint answered.
+fn is_endpoint_failure(err: &LLMPoolError) -> bool {
+    matches!(err, LLMPoolError::ProviderUnavailable(_) | LLMPoolError::DeadlineExceeded(_))
+}
+
+fn score(key: u64, url: &str) -> u64 {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    key.hash(&mut hasher);
+    url.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[async_trait]
+impl Provider for Balanced {
+    fn name(&self) -> &str {
+        &self.name
+    }
+    
+    fn supports(&self, task: &str) -> bool {
+        self.endpoints[0].provider.supports(task)
+    }
+    
+    async fn infer(&self, prompt: &str, params: &InferParams) -> Result<ProviderResponse> {
+        self.dispatch(self.affinity(prompt), params.deadline_ms, |p, ms| {
+            let params = InferParams { deadline_ms: ms, ..params.clone() };
+            async move { p.infer(prompt, &params).await }
+        })
+        .await
+    }
+    
+    async fn chat(&self, messages: &[ChatMessage], params: &InferParams) -> Result<ProviderResponse> {
+        // The conversation before the newest turn is what the engine has cached
+        let history = match messages {
+            [only] => std::slice::from_ref(only),
+            [history @ .., _] => history,
+            [] => &[],
+        };
+        let key = self.affinity(&history.iter().map(|m| (&m.role, &m.content)).collect::<Vec<_>>());
+        self.dispatch(key, params.deadline_ms, |p, ms| {
+            let params = InferParams { deadline_ms: ms, ..params.clone() };
+            async move { p.chat(messages, &params).await }
+        })
+        .await
+    }
+    
+    async fn embed(&self, inputs: &[String], params: &InferParams) -> Result<Vec<Vec<f32>>> {
+        self.dispatch(None, params.deadline_ms, |p, ms| {
+            let params = InferParams { deadline_ms: ms, ..params.clone() };
+            async move { p.embed(inputs, &params).await }
+        })
+        .await
+    }
+    
+    /// Loads the model on every endpoint; fine as long as one of them is up.
+    async fn warm_up(&self, timeout: Duration) -> Result<()> {
+        let mut warming = tokio::task::JoinSet::new();
+        for endpoint in &self.endpoints {
+            let (url, provider) = (endpoint.url.clone(), endpoint.provider.clone());
+            warming.spawn(async move { (url, provider.warm_up(timeout).await) });
+        }
+        
+        let mut last_error = None;
+        let mut warmed = 0;
+        while let Some(Ok((url, result))) = warming.join_next().await {
+            match result {
+                Ok(()) => warmed += 1,
+                Err(e) => {
+                    warn!("🔀 {} endpoint {} did not warm up: {}", self.name, url, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        match (warmed, last_error) {
+            (0, Some(e)) => Err(e),
+            _ => Ok(()),
+        }
+    }
+    
+    /// Models loaded on any endpoint.
+    async fn loaded_models(&self) -> Result<Vec<String>> {
+        let mut models = Vec::new();
+        let mut last_error = None;
+        for endpoint in &self.endpoints {
+            match endpoint.provider.loaded_models().await {
+                Ok(loaded) => models.extend(loaded),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        match last_error {
+            Some(e) if models.is_empty() => Err(e),
+            _ => {
+                models.sort();
+                models.dedup();
+                Ok(models)
+            }
+        }
+    }
+    
+    /// Probes every endpoint; healthy while any of them is.
+    async fn health(&self) -> bool {
+        let mut any = false;
+        for endpoint in &self.endpoints {
+            let healthy = endpoint.provider.health().await;
+            match (endpoint.healthy.swap(healthy, Ordering::Relaxed), healthy) {
+                (true, false) => warn!("🔀 {} endpoint {} is unhealthy", self.name, endpoint.url),
+                (false, true) => info!("🔀 {} endpoint {} is healthy again", self.name, endpoint.url),
+                _ => {}
+            }
+            any |= healthy;
+        }
+        any
+    }
+}
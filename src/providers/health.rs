@@ -1,2 +1,34 @@
-// Health checking and circuit breaker logic will be implemented here
-// For now, this is a placeholder
+use super::{Provider, ProviderPool};
+use crate::config::HealthConfig;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+/// Probes the endpoints of multi-endpoint providers so the balancer stops
+/// sending calls to a dead replica before they fail, and routes to it again
+/// once it answers. Single-endpoint providers have nothing to route around.
+pub fn spawn(pool: Arc<ProviderPool>, config: &HealthConfig) -> JoinHandle<()> {
+    let interval = Duration::from_millis(config.interval_ms);
+    info!("🩺 Probing endpoints of {} balanced providers every {}ms", pool.balanced.len(), config.interval_ms);
+    
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            let mut probes = tokio::task::JoinSet::new();
+            for (name, balanced) in &pool.balanced {
+                let (name, balanced) = (name.clone(), balanced.clone());
+                probes.spawn(async move {
+                    // Endpoints log their own changes; all of them down is worth a line
+                    if !balanced.health().await {
+                        warn!("🩺 No endpoint of {} is healthy", name);
+                    }
+                });
+            }
+            if tokio::time::timeout(interval, async { while probes.join_next().await.is_some() {} }).await.is_err() {
+                warn!("🩺 Endpoint probes took longer than {}ms", interval.as_millis());
+            }
+        }
+    })
+}
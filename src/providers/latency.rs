@@ -0,0 +1,207 @@
+use super::{ChatMessage, InferParams, Provider, ProviderResponse};
+use crate::config::{LatencyConfig, ProviderConfig};
+use crate::errors::{LLMPoolError, Result};
+use crate::metrics::metrics;
+use async_trait::async_trait;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Latency per provider and task, and calls in flight per provider, so
+/// FASTEST tries the provider expected to answer soonest instead of the
+/// first one in config order.
+pub struct LatencyTracker {
+    enabled: bool,
+    alpha: f64,
+    window: usize,
+    /// Provider → task → samples
+    stats: Mutex<HashMap<String, HashMap<String, Stats>>>,
+    /// Provider → (calls in flight, calls it serves at once)
+    load: HashMap<String, (AtomicUsize, usize)>,
+}
+
+struct Stats {
+    ewma_ms: f64,
+    recent: VecDeque<f64>,
+}
+
+impl LatencyTracker {
+    pub(super) fn new(config: &LatencyConfig, providers: &[ProviderConfig]) -> Self {
+        let load = providers.iter()
+            .map(|p| (p.name.clone(), (AtomicUsize::new(0), p.max_concurrency.max(1))))
+            .collect();
+        Self {
+            enabled: config.enabled,
+            alpha: config.ewma_alpha.clamp(0.01, 1.0),
+            window: config.window.max(1),
+            stats: Mutex::new(HashMap::new()),
+            load,
+        }
+    }
+    
+    fn record(&self, provider: &str, task: &str, ms: f64) {
+        let mut stats = self.stats.lock().unwrap();
+        let entry = stats.entry(provider.to_string())
+            .or_default()
+            .entry(task.to_string())
+            .or_insert_with(|| Stats { ewma_ms: ms, recent: VecDeque::new() });
+        entry.ewma_ms += self.alpha * (ms - entry.ewma_ms);
+        entry.recent.push_back(ms);
+        if entry.recent.len() > self.window {
+            entry.recent.pop_front();
+        }
+        
+        let mut sorted: Vec<f64> = entry.recent.iter().copied().collect();
+        sorted.sort_by(f64::total_cmp);
+        let p95 = sorted[((sorted.len() as f64 * 0.95).ceil() as usize).clamp(1, sorted.len()) - 1];
+        metrics().provider_latency_ewma
+            .with_label_values(&[provider, task])
+            .set(entry.ewma_ms / 1000.0);
+        metrics().provider_latency_p95
+            .with_label_values(&[provider, task])
+            .set(p95 / 1000.0);
+    }
+    
+    /// The moving average scaled up by the calls already in flight per slot.
+    /// Providers not yet measured on the task score 0 so they get tried.
+    fn expected_ms(&self, stats: &HashMap<String, HashMap<String, Stats>>, provider: &str, task: &str) -> f64 {
+        let Some(s) = stats.get(provider).and_then(|tasks| tasks.get(task)) else {
+            return 0.0;
+        };
+        let (in_flight, parallelism) = self.load.get(provider)
+            .map_or((0, 1), |(n, p)| (n.load(Ordering::Relaxed), *p));
+        s.ewma_ms * (1.0 + in_flight as f64 / parallelism as f64)
+    }
+    
+    /// Sorts providers by expected latency; ties keep their order.
+    pub(super) fn order(&self, mut providers: Vec<Arc<dyn Provider>>, task: &str) -> Vec<Arc<dyn Provider>> {
+        if !self.enabled || providers.len() < 2 {
+            return providers;
+        }
+        let stats = self.stats.lock().unwrap();
+        providers.sort_by(|a, b| {
+            self.expected_ms(&stats, a.name(), task)
+                .total_cmp(&self.expected_ms(&stats, b.name(), task))
+        });
+        providers
+    }
+    
+    fn start<'a>(&'a self, provider: &'a str, params: &'a InferParams) -> InFlight<'a> {
+        if let Some((in_flight, _)) = self.load.get(provider) {
+            in_flight.fetch_add(1, Ordering::Relaxed);
+            metrics().provider_in_flight.with_label_values(&[provider]).inc();
+        }
+        InFlight {
+            tracker: self,
+            provider,
+            task: &params.task,
+            deadline_ms: params.deadline_ms,
+            started: Instant::now(),
+            recorded: false,
+        }
+    }
+}
+
+/// One tracked call. Dropped unfinished (the ensemble gave up on it), the
+/// time it ran so far is charged as a sample.
+struct InFlight<'a> {
+    tracker: &'a LatencyTracker,
+    provider: &'a str,
+    task: &'a str,
+    deadline_ms: i32,
+    started: Instant,
+    recorded: bool,
+}
+
+impl InFlight<'_> {
+    /// Answers count their inference time; failures count as taking the
+    /// whole deadline so a failing provider loses the first slot. Shed calls
+    /// say nothing about speed and are not counted.
+    fn finish(mut self, result: &Result<ProviderResponse>) {
+        let ms = match result {
+            Ok(response) => response.duration_ms as f64,
+            Err(LLMPoolError::Overloaded(_)) => {
+                self.recorded = true;
+                return;
+            }
+            Err(_) => self.elapsed_ms().max(self.deadline_ms as f64),
+        };
+        self.tracker.record(self.provider, self.task, ms);
+        self.recorded = true;
+    }
+    
+    fn elapsed_ms(&self) -> f64 {
+        self.started.elapsed().as_secs_f64() * 1000.0
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        if let Some((in_flight, _)) = self.tracker.load.get(self.provider) {
+            in_flight.fetch_sub(1, Ordering::Relaxed);
+            metrics().provider_in_flight.with_label_values(&[self.provider]).dec();
+        }
+        if !self.recorded && !self.task.is_empty() {
+            self.tracker.record(self.provider, self.task, self.elapsed_ms());
+        }
+    }
+}
+
+/// Provider wrapper that feeds generation calls into the tracker. Embedding,
+/// warm-up and residency calls pass straight through.
+pub struct Timed {
+    inner: Arc<dyn Provider>,
+    tracker: Arc<LatencyTracker>,
+}
+
+impl Timed {
+    pub fn new(inner: Arc<dyn Provider>, tracker: Arc<LatencyTracker>) -> Self {
+        Self { inner, tracker }
+    }
+}
+
+#[async_trait]
+impl Provider for Timed {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    
+    fn supports(&self, task: &str) -> bool {
+        self.inner.supports(task)
+    }
+    
+    async fn infer(&self, prompt: &str, params: &InferParams) -> Result<ProviderResponse> {
+        let call = self.tracker.start(self.inner.name(), params);
+        let result = self.inner.infer(prompt, params).await;
+        if !params.task.is_empty() {
+            call.finish(&result);
+        }
+        result
+    }
+    
+    async fn chat(&self, messages: &[ChatMessage], params: &InferParams) -> Result<ProviderResponse> {
+        let call = self.tracker.start(self.inner.name(), params);
+        let result = self.inner.chat(messages, params).await;
+        if !params.task.is_empty() {
+            call.finish(&result);
+        }
+        result
+    }
+    
+    async fn embed(&self, inputs: &[String], params: &InferParams) -> Result<Vec<Vec<f32>>> {
+        self.inner.embed(inputs, params).await
+    }
+    
+    async fn warm_up(&self, timeout: Duration) -> Result<()> {
+        self.inner.warm_up(timeout).await
+    }
+    
+    async fn loaded_models(&self) -> Result<Vec<String>> {
+        self.inner.loaded_models().await
+    }
+    
+    async fn health(&self) -> bool {
+        self.inner.health().await
+    }
+}
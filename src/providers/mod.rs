@@ -1,11 +1,23 @@
 mod ollama;
-mod health;
+pub mod balancer;
+pub mod health;
+pub mod latency;
+pub mod residency;
+pub mod warmup;
 
-use crate::config::Config;
-use crate::errors::Result;
+use crate::config::{Config, Priority, ProviderConfig, Sampling};
+use crate::errors::{LLMPoolError, Result};
+use crate::qos::bulkhead::Bulkheaded;
 use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::Arc;
+use std::time::Duration;
+use self::balancer::Balanced;
+use self::latency::{LatencyTracker, Timed};
+use self::residency::Residency;
+use self::warmup::WarmupState;
 
 #[async_trait]
 pub trait Provider: Send + Sync {
@@ -13,8 +25,108 @@ pub trait Provider: Send + Sync {
     fn name(&self) -> &str;
     #[allow(dead_code)]
     fn supports(&self, task: &str) -> bool;
-    async fn infer(&self, prompt: &str, max_tokens: i32, deadline_ms: i32) -> Result<ProviderResponse>;
+    async fn infer(&self, prompt: &str, params: &InferParams) -> Result<ProviderResponse>;
     async fn health(&self) -> bool;
+    
+    /// Generates from role-tagged messages. Providers without a chat endpoint
+    /// keep the default, which sends the flattened transcript to `infer`.
+    async fn chat(&self, messages: &[ChatMessage], params: &InferParams) -> Result<ProviderResponse> {
+        self.infer(&flatten_messages(messages), params).await
+    }
+    
+    /// Returns one vector per input, in order. Only the deadline and the
+    /// queue identity in `params` apply. Providers without an embedding
+    /// endpoint keep the default.
+    async fn embed(&self, _inputs: &[String], _params: &InferParams) -> Result<Vec<Vec<f32>>> {
+        Err(LLMPoolError::ProviderError(
+            format!("Provider {} does not support embeddings", self.name())
+        ))
+    }
+    
+    /// Loads the model with a tiny request so real traffic does not wait for
+    /// it. `timeout` bounds the call, as a cold load can take far longer than
+    /// the provider's request timeout; the default cannot extend it.
+    async fn warm_up(&self, _timeout: Duration) -> Result<()> {
+        let params = InferParams { max_tokens: 1, ..Default::default() };
+        self.infer("ping", &params).await.map(|_| ())
+    }
+    
+    /// Models the provider's host currently holds in memory. Engines that
+    /// cannot tell keep the default and are never treated as resident.
+    async fn loaded_models(&self) -> Result<Vec<String>> {
+        Ok(Vec::new())
+    }
+}
+
+pub const CHAT_ROLES: [&str; 3] = ["system", "user", "assistant"];
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+        }
+    }
+}
+
+/// What a request asks the model: a plain prompt or a conversation.
+#[derive(Debug, Clone)]
+pub enum PromptInput {
+    Text(String),
+    Messages(Vec<ChatMessage>),
+}
+
+impl PromptInput {
+    pub async fn send(&self, provider: &dyn Provider, params: &InferParams) -> Result<ProviderResponse> {
+        match self {
+            PromptInput::Text(prompt) => provider.infer(prompt, params).await,
+            PromptInput::Messages(messages) => provider.chat(messages, params).await,
+        }
+    }
+}
+
+/// Renders messages as one prompt, identically for every provider:
+/// a `### role` header per message, then an open assistant turn.
+pub fn flatten_messages(messages: &[ChatMessage]) -> String {
+    let mut prompt = String::new();
+    for message in messages {
+        prompt.push_str("### ");
+        prompt.push_str(&message.role);
+        prompt.push('\n');
+        prompt.push_str(message.content.trim_end());
+        prompt.push_str("\n\n");
+    }
+    prompt.push_str("### assistant\n");
+    prompt
+}
+
+/// Per-call generation settings.
+#[derive(Debug, Clone, Default)]
+pub struct InferParams {
+    pub max_tokens: i32,
+    pub deadline_ms: i32,
+    /// Request and task sampling; providers fill the gaps from their own
+    /// defaults and clamp to their limits
+    pub sampling: Sampling,
+    /// JSON Schema the output must follow. Engines with constrained decoding
+    /// enforce it; others ignore it and rely on validation.
+    pub output_schema: Option<Arc<Value>>,
+    /// Base64 image attachments; only routed to "vision" providers
+    pub images: Arc<[String]>,
+    /// Queue class at providers with a concurrency limit
+    pub priority: Priority,
+    /// Fair-queuing identity and share at providers with a concurrency limit
+    pub tenant_id: String,
+    pub tenant_weight: f32,
+    /// Task the call serves, for per-task latency tracking; empty for
+    /// warm-up and other calls outside an ensemble
+    pub task: String,
 }
 
 #[derive(Debug, Clone)]
@@ -22,11 +134,20 @@ pub struct ProviderResponse {
     pub content: String,
     pub model: String,
     pub duration_ms: i32,
+    /// Time spent waiting for a concurrency slot, not included in duration_ms
+    pub queue_ms: i32,
 }
 
 pub struct ProviderPool {
     providers: HashMap<String, Arc<dyn Provider>>,
     task_map: HashMap<String, Vec<String>>,
+    /// Provider names per declared capability, in config order
+    capabilities: HashMap<String, Vec<String>>,
+    warmup: WarmupState,
+    residency: Residency,
+    latency: Arc<LatencyTracker>,
+    /// Providers with several endpoints
+    balanced: HashMap<String, Arc<Balanced>>,
 }
 
 impl ProviderPool {
@@ -38,6 +159,30 @@ impl ProviderPool {
         self.providers.get(name).cloned()
     }
     
+    pub fn providers_with(&self, capability: &str) -> Vec<Arc<dyn Provider>> {
+        self.capabilities
+            .get(capability)
+            .map(|names| names.iter().filter_map(|name| self.get(name)).collect())
+            .unwrap_or_default()
+    }
+    
+    pub fn has_capability(&self, name: &str, capability: &str) -> bool {
+        self.capabilities
+            .get(capability)
+            .is_some_and(|names| names.iter().any(|n| n == name))
+    }
+    
+    pub fn has_task(&self, task: &str) -> bool {
+        self.task_map.contains_key(task)
+    }
+    
+    /// Every task at least one provider serves, sorted.
+    pub fn tasks(&self) -> Vec<String> {
+        let mut tasks: Vec<String> = self.task_map.keys().cloned().collect();
+        tasks.sort();
+        tasks
+    }
+    
     pub fn providers_for_task(&self, task: &str) -> Vec<Arc<dyn Provider>> {
         self.task_map
             .get(task)
@@ -49,6 +194,42 @@ impl ProviderPool {
             .unwrap_or_default()
     }
     
+    /// Reorders and trims ensemble candidates by what their hosts have loaded.
+    pub fn place(&self, providers: Vec<Arc<dyn Provider>>) -> Vec<Arc<dyn Provider>> {
+        self.residency.place(providers)
+    }
+    
+    /// Orders FASTEST candidates by expected latency on the task.
+    pub fn by_latency(&self, providers: Vec<Arc<dyn Provider>>, task: &str) -> Vec<Arc<dyn Provider>> {
+        self.latency.order(providers, task)
+    }
+    
+    /// False until the startup warm-up pass has finished.
+    pub fn is_ready(&self) -> bool {
+        self.warmup.is_ready()
+    }
+    
+    pub fn warmup_status(&self) -> HashMap<String, String> {
+        self.warmup.snapshot()
+    }
+    
+    /// The driver behind one of a provider's endpoints; the provider itself
+    /// when it has a single one.
+    pub fn endpoint(&self, name: &str, url: &str) -> Option<Arc<dyn Provider>> {
+        match self.balanced.get(name) {
+            Some(balanced) => balanced.endpoint(url),
+            None => self.get(name),
+        }
+    }
+    
+    /// Per-endpoint state of providers with several endpoints, as of the
+    /// last health check.
+    pub fn endpoint_status(&self) -> HashMap<String, HashMap<String, String>> {
+        self.balanced.iter()
+            .map(|(name, balanced)| (name.clone(), balanced.status()))
+            .collect()
+    }
+    
     pub async fn health_check(&self) -> HashMap<String, bool> {
         let mut results = HashMap::new();
         for (name, provider) in &self.providers {
@@ -61,28 +242,85 @@ impl ProviderPool {
 pub async fn init(config: &Config) -> Result<Arc<ProviderPool>> {
     let mut providers: HashMap<String, Arc<dyn Provider>> = HashMap::new();
     let mut task_map: HashMap<String, Vec<String>> = HashMap::new();
+    let mut capabilities: HashMap<String, Vec<String>> = HashMap::new();
+    let latency = Arc::new(LatencyTracker::new(&config.latency, &config.providers));
+    
+    let mut balanced = HashMap::new();
     
     for pconfig in &config.providers {
-        let provider: Arc<dyn Provider> = match pconfig.driver.as_str() {
-            "ollama" => Arc::new(ollama::OllamaProvider::new(pconfig.clone())),
-            _ => {
-                tracing::warn!("Unknown provider driver: {}", pconfig.driver);
-                continue;
-            }
+        let provider = if pconfig.endpoints.is_empty() {
+            driver(pconfig)
+        } else {
+            // One driver per replica, balanced as a single provider
+            pconfig.endpoints.iter()
+                .map(|url| {
+                    let endpoint = ProviderConfig { base_url: url.clone(), endpoints: Vec::new(), ..pconfig.clone() };
+                    driver(&endpoint).map(|provider| (url.clone(), provider))
+                })
+                .collect::<Option<Vec<_>>>()
+                .map(|endpoints| {
+                    let replicas = Arc::new(Balanced::new(pconfig, &config.breaker, endpoints));
+                    balanced.insert(pconfig.name.clone(), replicas.clone());
+                    replicas as Arc<dyn Provider>
+                })
+        };
+        let Some(mut provider) = provider else {
+            tracing::warn!("Unknown provider driver: {}", pconfig.driver);
+            continue;
         };
+        if pconfig.max_concurrency > 0 {
+            provider = Arc::new(Bulkheaded::new(provider, pconfig.max_concurrency, pconfig.max_queue));
+        }
+        if config.latency.enabled {
+            provider = Arc::new(Timed::new(provider, latency.clone()));
+        }
         
         // Map tasks to this provider
         for task in &pconfig.tasks {
             task_map.entry(task.clone())
-                .or_insert_with(Vec::new)
+                .or_default()
+                .push(pconfig.name.clone());
+        }
+        
+        for capability in &pconfig.capabilities {
+            capabilities.entry(capability.clone())
+                .or_default()
                 .push(pconfig.name.clone());
         }
         
         providers.insert(pconfig.name.clone(), provider);
     }
     
+    // Routing from [tasks.<name>] providers lists
+    for (task, task_config) in &config.tasks {
+        for name in &task_config.providers {
+            if !providers.contains_key(name) {
+                tracing::warn!("Task {} lists unknown provider: {}", task, name);
+                continue;
+            }
+            let names = task_map.entry(task.clone()).or_default();
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+    }
+    
+    let warmup = WarmupState::new(providers.keys(), config.warmup.enabled);
+    let residency = Residency::new(&config.residency, &config.providers);
     Ok(Arc::new(ProviderPool {
         providers,
         task_map,
+        capabilities,
+        warmup,
+        residency,
+        latency,
+        balanced,
     }))
 }
+
+fn driver(config: &ProviderConfig) -> Option<Arc<dyn Provider>> {
+    match config.driver.as_str() {
+        "ollama" => Some(Arc::new(ollama::OllamaProvider::new(config.clone()))),
+        _ => None,
+    }
+}
@@ -1,9 +1,13 @@
-use super::{Provider, ProviderResponse};
-use crate::config::ProviderConfig;
+use super::{ChatMessage, InferParams, Provider, ProviderResponse};
+use crate::config::{ProviderConfig, Sampling};
 use crate::errors::{LLMPoolError, Result};
 use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
-use std::time::Instant;
+use std::time::{Duration, Instant};
+
+/// Sampling temperature when neither the request, the task nor the provider
+/// config sets one
+const DEFAULT_TEMPERATURE: f32 = 0.3;
 
 pub struct OllamaProvider {
     config: ProviderConfig,
@@ -11,17 +15,25 @@ pub struct OllamaProvider {
 }
 
 #[derive(Serialize)]
-struct OllamaRequest {
+struct OllamaRequest<'a> {
     model: String,
     prompt: String,
     stream: bool,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    images: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
     options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
 }
 
+/// Ollama's option names match `Sampling`'s fields, so it is sent as-is.
 #[derive(Serialize)]
 struct OllamaOptions {
     num_predict: i32,
-    temperature: f32,
+    #[serde(flatten)]
+    sampling: Sampling,
 }
 
 #[derive(Deserialize)]
@@ -29,8 +41,59 @@ struct OllamaResponse {
     response: String,
 }
 
+#[derive(Serialize)]
+struct OllamaChatRequest<'a> {
+    model: &'a str,
+    messages: Vec<OllamaChatMessage<'a>>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<serde_json::Value>,
+    options: OllamaOptions,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
+}
+
+/// Ollama attaches images to a message rather than to the request.
+#[derive(Serialize)]
+struct OllamaChatMessage<'a> {
+    role: &'a str,
+    content: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    images: &'a [String],
+}
+
+#[derive(Deserialize)]
+struct OllamaChatResponse {
+    message: ChatMessage,
+}
+
+#[derive(Deserialize)]
+struct OllamaPsResponse {
+    models: Vec<OllamaLoadedModel>,
+}
+
+#[derive(Deserialize)]
+struct OllamaLoadedModel {
+    name: String,
+}
+
+#[derive(Serialize)]
+struct OllamaEmbedRequest<'a> {
+    model: &'a str,
+    input: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    keep_alive: Option<&'a str>,
+}
+
+#[derive(Deserialize)]
+struct OllamaEmbedResponse {
+    embeddings: Vec<Vec<f32>>,
+}
+
 impl OllamaProvider {
-    pub fn new(config: ProviderConfig) -> Self {
+    pub fn new(mut config: ProviderConfig) -> Self {
+        // Paths are appended with their own leading slash
+        config.base_url = config.base_url.trim_end_matches('/').to_string();
         let client = reqwest::Client::builder()
             .timeout(std::time::Duration::from_millis(
                 config.timeout_ms.unwrap_or(5000) as u64
@@ -40,6 +103,38 @@ impl OllamaProvider {
         
         Self { config, client }
     }
+    
+    /// A POST cut off at the call's deadline when that is shorter than the
+    /// client's `timeout_ms`, e.g. after time spent queuing.
+    fn post(&self, url: &str, params: &InferParams) -> reqwest::RequestBuilder {
+        let request = self.client.post(url);
+        let timeout_ms = self.config.timeout_ms.unwrap_or(5000);
+        match params.deadline_ms {
+            ms if ms > 0 && ms < timeout_ms => request.timeout(std::time::Duration::from_millis(ms as u64)),
+            _ => request,
+        }
+    }
+    
+    /// The `format` field for a request: the full schema, plain JSON mode,
+    /// or nothing, depending on what the configured engine supports.
+    fn format_for(&self, params: &InferParams) -> Option<serde_json::Value> {
+        let schema = params.output_schema.as_ref()?;
+        match self.config.structured_output.as_str() {
+            "schema" => Some(schema.as_ref().clone()),
+            "json" => Some(serde_json::Value::from("json")),
+            _ => None,
+        }
+    }
+    
+    /// Request sampling over the provider defaults, clamped to its limits.
+    fn options_for(&self, params: &InferParams) -> OllamaOptions {
+        let mut sampling = self.config.limits.apply(params.sampling.or(&self.config.sampling));
+        sampling.temperature.get_or_insert(DEFAULT_TEMPERATURE);
+        OllamaOptions {
+            num_predict: params.max_tokens,
+            sampling,
+        }
+    }
 }
 
 #[async_trait]
@@ -52,32 +147,29 @@ impl Provider for OllamaProvider {
         self.config.tasks.iter().any(|t| t == task)
     }
     
-    async fn infer(&self, prompt: &str, max_tokens: i32, _deadline_ms: i32) -> Result<ProviderResponse> {
+    async fn infer(&self, prompt: &str, params: &InferParams) -> Result<ProviderResponse> {
         let start = Instant::now();
         
         let request = OllamaRequest {
             model: self.config.model.clone(),
             prompt: prompt.to_string(),
             stream: false,
-            options: OllamaOptions {
-                num_predict: max_tokens,
-                temperature: 0.3,
-            },
+            images: &params.images,
+            format: self.format_for(params),
+            options: self.options_for(params),
+            keep_alive: self.config.keep_alive.as_deref(),
         };
         
         let url = format!("{}/api/generate", self.config.base_url);
         
-        let response = self.client
-            .post(&url)
+        let response = self.post(&url, params)
             .json(&request)
             .send()
             .await
-            .map_err(|e| LLMPoolError::ProviderError(format!("Ollama request failed: {}", e)))?;
+            .map_err(|e| send_failed("Ollama request", e))?;
         
         if !response.status().is_success() {
-            return Err(LLMPoolError::ProviderError(
-                format!("Ollama returned status: {}", response.status())
-            ));
+            return Err(status_failed("Ollama", response.status()));
         }
         
         let ollama_resp: OllamaResponse = response
@@ -91,11 +183,178 @@ impl Provider for OllamaProvider {
             content: ollama_resp.response,
             model: self.config.model.clone(),
             duration_ms,
+            queue_ms: 0,
+        })
+    }
+    
+    async fn chat(&self, messages: &[ChatMessage], params: &InferParams) -> Result<ProviderResponse> {
+        let start = Instant::now();
+        
+        // Request images go with the last user turn, or the last message of a
+        // conversation without one
+        let image_turn = messages.iter()
+            .rposition(|m| m.role == "user")
+            .or(messages.len().checked_sub(1));
+        let messages = messages.iter().enumerate()
+            .map(|(i, m)| OllamaChatMessage {
+                role: &m.role,
+                content: &m.content,
+                images: if Some(i) == image_turn { &params.images } else { &[] },
+            })
+            .collect();
+        
+        let request = OllamaChatRequest {
+            model: &self.config.model,
+            messages,
+            stream: false,
+            format: self.format_for(params),
+            options: self.options_for(params),
+            keep_alive: self.config.keep_alive.as_deref(),
+        };
+        
+        let url = format!("{}/api/chat", self.config.base_url);
+        
+        let response = self.post(&url, params)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| send_failed("Ollama chat request", e))?;
+        
+        if !response.status().is_success() {
+            return Err(status_failed("Ollama chat", response.status()));
+        }
+        
+        let chat_resp: OllamaChatResponse = response
+            .json()
+            .await
+            .map_err(|e| LLMPoolError::ProviderError(format!("Failed to parse Ollama chat response: {}", e)))?;
+        
+        Ok(ProviderResponse {
+            content: chat_resp.message.content,
+            model: self.config.model.clone(),
+            duration_ms: start.elapsed().as_millis() as i32,
+            queue_ms: 0,
         })
     }
     
+    async fn embed(&self, inputs: &[String], params: &InferParams) -> Result<Vec<Vec<f32>>> {
+        let request = OllamaEmbedRequest {
+            model: &self.config.model,
+            input: inputs,
+            keep_alive: self.config.keep_alive.as_deref(),
+        };
+        
+        let url = format!("{}/api/embed", self.config.base_url);
+        
+        let response = self.post(&url, params)
+            .json(&request)
+            .send()
+            .await
+            .map_err(|e| send_failed("Ollama embed request", e))?;
+        
+        if !response.status().is_success() {
+            return Err(status_failed("Ollama embed", response.status()));
+        }
+        
+        let embed_resp: OllamaEmbedResponse = response
+            .json()
+            .await
+            .map_err(|e| LLMPoolError::ProviderError(format!("Failed to parse Ollama embed response: {}", e)))?;
+        
+        if embed_resp.embeddings.len() != inputs.len() {
+            return Err(LLMPoolError::ProviderError(format!(
+                "Ollama returned {} embeddings for {} inputs",
+                embed_resp.embeddings.len(),
+                inputs.len()
+            )));
+        }
+        
+        Ok(embed_resp.embeddings)
+    }
+    
+    async fn warm_up(&self, timeout: Duration) -> Result<()> {
+        let ping = ["ping".to_string()];
+        let params = InferParams { max_tokens: 1, ..Default::default() };
+        let keep_alive = self.config.keep_alive.as_deref();
+        
+        // Providers without tasks (or shadow tasks) serve embeddings only, and
+        // embedding models cannot generate, so load them through /api/embed
+        let request = if self.config.tasks.is_empty() && self.config.shadow.is_none() {
+            self.client
+                .post(format!("{}/api/embed", self.config.base_url))
+                .json(&OllamaEmbedRequest { model: &self.config.model, input: &ping, keep_alive })
+        } else {
+            self.client
+                .post(format!("{}/api/generate", self.config.base_url))
+                .json(&OllamaRequest {
+                    model: self.config.model.clone(),
+                    prompt: ping[0].clone(),
+                    stream: false,
+                    images: &[],
+                    format: None,
+                    options: self.options_for(&params),
+                    keep_alive,
+                })
+        };
+        
+        let response = request
+            .timeout(timeout)
+            .send()
+            .await
+            .map_err(|e| LLMPoolError::ProviderError(format!("Ollama warm-up failed: {}", e)))?;
+        
+        if !response.status().is_success() {
+            return Err(LLMPoolError::ProviderError(
+                format!("Ollama warm-up returned status: {}", response.status())
+            ));
+        }
+        Ok(())
+    }
+    
+    async fn loaded_models(&self) -> Result<Vec<String>> {
+        let url = format!("{}/api/ps", self.config.base_url);
+        
+        let response = self.client
+            .get(&url)
+            .send()
+            .await
+            .map_err(|e| LLMPoolError::ProviderError(format!("Ollama ps request failed: {}", e)))?;
+        
+        if !response.status().is_success() {
+            return Err(LLMPoolError::ProviderError(
+                format!("Ollama ps returned status: {}", response.status())
+            ));
+        }
+        
+        let ps: OllamaPsResponse = response
+            .json()
+            .await
+            .map_err(|e| LLMPoolError::ProviderError(format!("Failed to parse Ollama ps response: {}", e)))?;
+        
+        Ok(ps.models.into_iter().map(|m| m.name).collect())
+    }
+    
     async fn health(&self) -> bool {
         let url = format!("{}/api/tags", self.config.base_url);
         self.client.get(&url).send().await.is_ok()
     }
 }
+
+/// Connection failures and timeouts may clear up on their own; other send
+/// errors will not.
+fn send_failed(what: &str, e: reqwest::Error) -> LLMPoolError {
+    let msg = format!("{} failed: {}", what, e);
+    if e.is_connect() || e.is_timeout() {
+        LLMPoolError::ProviderUnavailable(msg)
+    } else {
+        LLMPoolError::ProviderError(msg)
+    }
+}
+
+fn status_failed(what: &str, status: reqwest::StatusCode) -> LLMPoolError {
+    let msg = format!("{} returned status: {}", what, status);
+    match status.as_u16() {
+        429 | 502 | 503 | 504 => LLMPoolError::ProviderUnavailable(msg),
+        _ => LLMPoolError::ProviderError(msg),
+    }
+}
@@ -0,0 +1,315 @@
+use super::{Provider, ProviderPool};
+use crate::config::{ProviderConfig, ResidencyConfig};
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, info, warn};
+
+/// Which models each host has in memory, so ensembles prefer models that are
+/// already loaded and do not ask one host for more models than it can hold.
+pub struct Residency {
+    enabled: bool,
+    max_models_per_host: usize,
+    /// Provider name → (hosts of its endpoints, model)
+    placement: HashMap<String, (Vec<String>, String)>,
+    /// Provider name → position in the config
+    rank: HashMap<String, usize>,
+    /// Host → models it reported as loaded
+    resident: RwLock<HashMap<String, HashSet<String>>>,
+}
+
+impl Residency {
+    pub(super) fn new(config: &ResidencyConfig, providers: &[ProviderConfig]) -> Self {
+        let placement = providers.iter()
+            .map(|p| {
+                let hosts = p.endpoint_urls().into_iter().map(host_of).collect();
+                (p.name.clone(), (hosts, model_id(&p.model)))
+            })
+            .collect();
+        let rank = providers.iter().enumerate().map(|(i, p)| (p.name.clone(), i)).collect();
+        Self {
+            enabled: config.enabled,
+            max_models_per_host: config.max_models_per_host,
+            placement,
+            rank,
+            resident: RwLock::new(HashMap::new()),
+        }
+    }
+    
+    /// Orders resident providers first and, with a per-host cap, drops the
+    /// ones whose model would push their host over it. At least one provider
+    /// is always kept so the request can run. A provider with several
+    /// endpoints counts as resident, and fits, if any of its hosts does.
+    pub(super) fn place(&self, providers: Vec<Arc<dyn Provider>>) -> Vec<Arc<dyn Provider>> {
+        if !self.enabled || providers.len() < 2 {
+            return providers;
+        }
+        
+        let ordered = self.resident_first(providers);
+        let mut placed = self.within_caps(&ordered);
+        if placed.is_empty() {
+            placed.push(ordered[0].clone());
+        }
+        placed
+    }
+    
+    /// Providers the warm-up may load, in `place` order with config order
+    /// behind residency. Unlike `place`, a provider that does not fit is
+    /// left cold even if that leaves none, as loading it would evict a model
+    /// another provider needs.
+    pub(super) fn warm_set(&self, mut providers: Vec<Arc<dyn Provider>>) -> Vec<Arc<dyn Provider>> {
+        if !self.enabled {
+            return providers;
+        }
+        providers.sort_by_key(|p| self.rank.get(p.name()).copied().unwrap_or(usize::MAX));
+        self.within_caps(&self.resident_first(providers))
+    }
+    
+    /// Stable sort, so providers keep their order within each group.
+    fn resident_first(&self, mut providers: Vec<Arc<dyn Provider>>) -> Vec<Arc<dyn Provider>> {
+        let resident = self.resident.read().unwrap();
+        let is_resident = |p: &Arc<dyn Provider>| {
+            self.placement.get(p.name()).is_some_and(|(hosts, model)| {
+                hosts.iter().any(|host| resident.get(host).is_some_and(|m| m.contains(model)))
+            })
+        };
+        providers.sort_by_key(|p| !is_resident(p));
+        providers
+    }
+    
+    /// Drops, in order, the providers whose model would push every one of
+    /// their hosts over the cap.
+    fn within_caps(&self, ordered: &[Arc<dyn Provider>]) -> Vec<Arc<dyn Provider>> {
+        if self.max_models_per_host == 0 {
+            return ordered.to_vec();
+        }
+        
+        let resident = self.resident.read().unwrap();
+        let mut loaded: HashMap<&str, HashSet<&str>> = resident.iter()
+            .map(|(host, models)| (host.as_str(), models.iter().map(String::as_str).collect()))
+            .collect();
+        let mut placed = Vec::with_capacity(ordered.len());
+        for provider in ordered {
+            let Some((hosts, model)) = self.placement.get(provider.name()) else {
+                placed.push(provider.clone());
+                continue;
+            };
+            let fits = |models: &HashSet<&str>| models.contains(model.as_str()) || models.len() < self.max_models_per_host;
+            let host = hosts.iter()
+                .find(|host| loaded.get(host.as_str()).is_none_or(fits));
+            match host {
+                Some(host) => {
+                    loaded.entry(host).or_default().insert(model);
+                    placed.push(provider.clone());
+                }
+                None => debug!("🏠 Skipping {}: its hosts already hold {} models", provider.name(), self.max_models_per_host),
+            }
+        }
+        placed
+    }
+}
+
+/// Polls every host for its loaded models.
+pub fn spawn(pool: Arc<ProviderPool>, config: &ResidencyConfig) -> JoinHandle<()> {
+    let interval = Duration::from_millis(config.refresh_interval_ms);
+    let probes = probes(&pool);
+    info!("🏠 Tracking model residency on {} hosts", probes.len());
+    
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        // Hosts whose last poll failed; only changes are worth a warning
+        let mut unreachable: HashSet<String> = HashSet::new();
+        loop {
+            ticker.tick().await;
+            poll(&pool, &probes, interval, &mut unreachable).await;
+        }
+    })
+}
+
+/// Reads every host's loaded models once, so the startup warm-up can count
+/// them against the cap before the poller has run.
+pub(super) async fn refresh(pool: &ProviderPool, timeout: Duration) {
+    if pool.residency.enabled {
+        poll(pool, &probes(pool), timeout, &mut HashSet::new()).await;
+    }
+}
+
+/// One provider per host is enough to ask what the host has loaded.
+fn probes(pool: &ProviderPool) -> HashMap<String, Arc<dyn Provider>> {
+    let mut probes: HashMap<String, Arc<dyn Provider>> = HashMap::new();
+    for (name, (hosts, _)) in &pool.residency.placement {
+        for host in hosts {
+            if let Some(provider) = pool.endpoint(name, host) {
+                probes.entry(host.clone()).or_insert(provider);
+            }
+        }
+    }
+    probes
+}
+
+async fn poll(
+    pool: &ProviderPool,
+    probes: &HashMap<String, Arc<dyn Provider>>,
+    timeout: Duration,
+    unreachable: &mut HashSet<String>,
+) {
+    for (host, probe) in probes {
+        let failure = match tokio::time::timeout(timeout, probe.loaded_models()).await {
+            Ok(Ok(models)) => {
+                let models = models.iter().map(|m| model_id(m)).collect();
+                pool.residency.resident.write().unwrap().insert(host.clone(), models);
+                if unreachable.remove(host) {
+                    info!("🏠 Reading loaded models on {} again", host);
+                }
+                continue;
+            }
+            Ok(Err(e)) => e.to_string(),
+            Err(_) => "timed out".to_string(),
+        };
+        if unreachable.insert(host.clone()) {
+            warn!("🏠 Could not read loaded models on {}: {}", host, failure);
+        } else {
+            debug!("🏠 Still cannot read loaded models on {}: {}", host, failure);
+        }
+    }
+}
+
+fn host_of(base_url: &str) -> String {
+    base_url.trim_end_matches('/').to_string()
+}
+
+/// Ollama reports untagged models with ":latest".
+fn model_id(model: &str) -> String {
+    if model.contains(':') {
+        model.to_string()
+    } else {
+        format!("{}:latest", model)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::providers::{self, InferParams};
+    
+    /// A fake Ollama host reporting `loaded` from /api/ps.
+    async fn host(loaded: &[&str]) -> mockito::ServerGuard {
+        let mut server = mockito::Server::new_async().await;
+        let models: Vec<_> = loaded.iter().map(|m| serde_json::json!({ "name": m, "model": m })).collect();
+        server.mock("GET", "/api/ps")
+            .with_header("content-type", "application/json")
+            .with_body(serde_json::json!({ "models": models }).to_string())
+            .expect_at_least(1)
+            .create_async()
+            .await;
+        server
+    }
+    
+    fn config(a: &str, b: &str) -> Config {
+        let provider = |name: &str, url: &str, model: &str| format!(
+            "[[providers]]\nname = \"{}\"\ndriver = \"ollama\"\nbase_url = \"{}\"\nmodel = \"{}\"\ntasks = [\"t\"]\n",
+            name, url, model
+        );
+        let toml = [
+            "[server]\n[qos]\n[ensemble]\n[breaker]\n[cache]\n".to_string(),
+            "[warmup]\nenabled = false\n[residency]\nrefresh_interval_ms = 50\nmax_models_per_host = 2\n".to_string(),
+            // Config order is the order ensembles would use without residency
+            provider("llama-a", &format!("{}/", a), "llama3.1:8b"),
+            provider("qwen-b", b, "qwen2.5:7b"),
+            provider("phi3-a", a, "phi3:mini"),
+            provider("gemma-a", a, "gemma2:9b"),
+            provider("llama-b", b, "llama3.1"),
+        ]
+        .concat();
+        toml::from_str(&toml).unwrap()
+    }
+    
+    fn names(providers: &[Arc<dyn Provider>]) -> Vec<&str> {
+        providers.iter().map(|p| p.name()).collect()
+    }
+    
+    #[tokio::test]
+    async fn places_resident_models_first_within_host_caps() {
+        let mut a = host(&["phi3:mini"]).await;
+        let b = host(&["llama3.1:latest", "gemma2:9b"]).await;
+        let generate = a.mock("POST", "/api/generate")
+            .match_body(mockito::Matcher::PartialJsonString(r#"{"model": "phi3:mini"}"#.to_string()))
+            .with_body(r#"{"response": "ok", "done": true}"#)
+            .create_async()
+            .await;
+        
+        let config = config(&a.url(), &b.url());
+        let pool = providers::init(&config).await.unwrap();
+        
+        // Trailing slashes aside, providers on one base_url share a host
+        let hosts: HashSet<&Vec<String>> = pool.residency.placement.values().map(|(hosts, _)| hosts).collect();
+        assert_eq!(hosts.len(), 2);
+        
+        let polling = spawn(pool.clone(), &config.residency);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        polling.abort();
+        
+        // Resident phi3-a and llama-b ("llama3.1" is "llama3.1:latest") go
+        // first; llama-a then fills host A's second slot, so gemma-a does not
+        // fit even though gemma is loaded on B, and qwen-b would be a third
+        // model on B
+        let placed = pool.place(pool.providers_for_task("t"));
+        assert_eq!(names(&placed), ["phi3-a", "llama-b", "llama-a"]);
+        
+        // The first choice is served by the host that has it loaded
+        let params = InferParams { max_tokens: 1, ..Default::default() };
+        assert_eq!(placed[0].infer("hi", &params).await.unwrap().content, "ok");
+        generate.assert_async().await;
+    }
+    
+    #[tokio::test]
+    async fn keeps_one_provider_when_every_host_is_full() {
+        let a = host(&["mistral:7b", "gemma2:2b"]).await;
+        let b = host(&["mistral:7b", "gemma2:2b"]).await;
+        let config = config(&a.url(), &b.url());
+        let pool = providers::init(&config).await.unwrap();
+        
+        let polling = spawn(pool.clone(), &config.residency);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        polling.abort();
+        
+        let placed = pool.place(pool.providers_for_task("t"));
+        assert_eq!(names(&placed), ["llama-a"]);
+    }
+    
+    #[tokio::test]
+    async fn warm_up_loads_each_host_only_up_to_its_cap() {
+        let mut a = host(&["phi3:mini"]).await;
+        let mut b = host(&["llama3.1:latest", "gemma2:9b"]).await;
+        let generate = |server: &mut mockito::ServerGuard, model: &str, hits: usize| {
+            server.mock("POST", "/api/generate")
+                .match_body(mockito::Matcher::PartialJsonString(format!(r#"{{"model": "{}"}}"#, model)))
+                .with_body(r#"{"response": "ok", "done": true}"#)
+                .expect(hits)
+                .create()
+        };
+        // Same choice as an ensemble: phi3 and llama on A, llama on B
+        let warmed = [
+            generate(&mut a, "phi3:mini", 1),
+            generate(&mut a, "llama3.1:8b", 1),
+            generate(&mut a, "gemma2:9b", 0),
+            generate(&mut b, "llama3.1", 1),
+            generate(&mut b, "qwen2.5:7b", 0),
+        ];
+        
+        let mut config = config(&a.url(), &b.url());
+        config.warmup.keep_warm_interval_seconds = 0;
+        let pool = providers::init(&config).await.unwrap();
+        providers::warmup::spawn(pool.clone(), &config.warmup).await.unwrap();
+        
+        for mock in &warmed {
+            mock.assert();
+        }
+        let status = pool.warmup_status();
+        assert!(status["gemma-a"].starts_with("skipped"));
+        assert!(status["qwen-b"].starts_with("skipped"));
+        assert!(status["llama-b"].starts_with("warm"));
+    }
+}
@@ -0,0 +1,120 @@
+use super::{residency, Provider, ProviderPool};
+use crate::config::WarmupConfig;
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone)]
+pub enum WarmupStatus {
+    Pending,
+    Warm { duration_ms: u64 },
+    Failed(String),
+    /// Left cold because its host was at `max_models_per_host`
+    Skipped,
+}
+
+impl fmt::Display for WarmupStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WarmupStatus::Pending => write!(f, "pending"),
+            WarmupStatus::Warm { duration_ms } => write!(f, "warm ({}ms)", duration_ms),
+            WarmupStatus::Failed(error) => write!(f, "failed: {}", error),
+            WarmupStatus::Skipped => write!(f, "skipped: host at max_models_per_host"),
+        }
+    }
+}
+
+/// Per-provider warm-up results. The pool is ready once the startup pass has
+/// finished, whether or not every model loaded.
+pub struct WarmupState {
+    status: RwLock<HashMap<String, WarmupStatus>>,
+    ready: AtomicBool,
+}
+
+impl WarmupState {
+    pub(super) fn new<'a>(names: impl Iterator<Item = &'a String>, enabled: bool) -> Self {
+        Self {
+            status: RwLock::new(names.map(|n| (n.clone(), WarmupStatus::Pending)).collect()),
+            ready: AtomicBool::new(!enabled),
+        }
+    }
+    
+    pub fn is_ready(&self) -> bool {
+        self.ready.load(Ordering::Acquire)
+    }
+    
+    pub fn snapshot(&self) -> HashMap<String, String> {
+        self.status.read().unwrap()
+            .iter()
+            .map(|(name, status)| (name.clone(), status.to_string()))
+            .collect()
+    }
+    
+    fn set(&self, name: &str, status: WarmupStatus) {
+        self.status.write().unwrap().insert(name.to_string(), status);
+    }
+}
+
+/// Runs the startup warm-up and then, when configured, the keep-warm pings.
+pub fn spawn(pool: Arc<ProviderPool>, config: &WarmupConfig) -> JoinHandle<()> {
+    let timeout = Duration::from_millis(config.timeout_ms);
+    let interval = Duration::from_secs(config.keep_warm_interval_seconds);
+    let tasks = config.keep_warm_tasks.clone();
+    
+    tokio::spawn(async move {
+        // Models already loaded count against [residency] max_models_per_host
+        residency::refresh(&pool, timeout).await;
+        let providers = pool.residency.warm_set(pool.providers.values().cloned().collect());
+        info!("🔥 Warming up {} of {} providers", providers.len(), pool.providers.len());
+        for name in pool.providers.keys() {
+            if !providers.iter().any(|p| p.name() == name) {
+                pool.warmup.set(name, WarmupStatus::Skipped);
+            }
+        }
+        warm_all(&pool, providers, timeout).await;
+        pool.warmup.ready.store(true, Ordering::Release);
+        info!("✅ Warm-up complete");
+        
+        if interval.is_zero() || tasks.is_empty() {
+            return;
+        }
+        let mut names: Vec<&String> = tasks.iter()
+            .filter_map(|task| pool.task_map.get(task))
+            .flatten()
+            .collect();
+        names.sort();
+        names.dedup();
+        let providers: Vec<_> = names.into_iter().filter_map(|name| pool.get(name)).collect();
+        
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            warm_all(&pool, pool.residency.warm_set(providers.clone()), timeout).await;
+        }
+    })
+}
+
+/// Pings the providers concurrently; one slow model must not hold up the rest.
+async fn warm_all(pool: &Arc<ProviderPool>, providers: Vec<Arc<dyn Provider>>, timeout: Duration) {
+    let mut pings = tokio::task::JoinSet::new();
+    for provider in providers {
+        let pool = pool.clone();
+        pings.spawn(async move {
+            let start = Instant::now();
+            let status = match provider.warm_up(timeout).await {
+                Ok(()) => WarmupStatus::Warm { duration_ms: start.elapsed().as_millis() as u64 },
+                Err(e) => {
+                    warn!("🔥 Warm-up failed for {}: {}", provider.name(), e);
+                    WarmupStatus::Failed(e.to_string())
+                }
+            };
+            pool.warmup.set(provider.name(), status);
+        });
+    }
+    while pings.join_next().await.is_some() {}
+}
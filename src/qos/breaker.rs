@@ -1,2 +1,170 @@
-// Circuit breaker implementation
-// TODO: Track failure rates and open/close circuit per provider
+use crate::config::BreakerConfig;
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Calls a window must hold before its failure rate is trusted
+const MIN_CALLS: usize = 10;
+
+/// Failure-rate circuit breaker over the last `window_size` calls.
+///
+/// Once more than `fail_rate` of them failed, the breaker opens and refuses
+/// calls for `open_cooldown_ms`. After that it lets one trial call through:
+/// success closes it with a fresh window, failure opens it again. While open,
+/// only the trial's report counts; calls let through before it opened
+/// report into nothing.
+pub struct CircuitBreaker {
+    fail_rate: f32,
+    window_size: usize,
+    cooldown: Duration,
+    state: Mutex<State>,
+}
+
+#[derive(Default)]
+struct State {
+    /// true for each failed call, oldest first
+    outcomes: VecDeque<bool>,
+    failures: usize,
+    opened_at: Option<Instant>,
+    /// Id and start of the current trial call while open. A trial that
+    /// never reports back stops blocking the next one after another cooldown.
+    trial: Option<(u64, Instant)>,
+    next_trial: u64,
+}
+
+/// Leave for one call from `allow()`, handed back to `record()`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[must_use]
+pub struct Ticket {
+    /// Set for the trial call of an open breaker
+    trial: Option<u64>,
+}
+
+/// How a recorded outcome moved the breaker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transition {
+    Opened,
+    Closed,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &BreakerConfig) -> Self {
+        Self {
+            fail_rate: config.fail_rate,
+            window_size: config.window_size.max(1),
+            cooldown: Duration::from_millis(config.open_cooldown_ms),
+            state: Mutex::new(State::default()),
+        }
+    }
+    
+    /// True while calls are refused outright, i.e. open and not yet due a trial.
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        !self.trial_due(&state)
+    }
+    
+    /// A ticket if a call may go through now. While open, claims the trial call.
+    pub fn allow(&self) -> Option<Ticket> {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.is_none() {
+            return Some(Ticket { trial: None });
+        }
+        if self.trial_due(&state) {
+            let id = state.next_trial;
+            state.next_trial += 1;
+            state.trial = Some((id, Instant::now()));
+            return Some(Ticket { trial: Some(id) });
+        }
+        None
+    }
+    
+    pub fn record(&self, ticket: Ticket, ok: bool) -> Option<Transition> {
+        let mut state = self.state.lock().unwrap();
+        if state.opened_at.is_some() {
+            let is_trial = ticket.trial.is_some() && ticket.trial == state.trial.map(|(id, _)| id);
+            if !is_trial {
+                return None;
+            }
+            if ok {
+                let next_trial = state.next_trial;
+                *state = State { next_trial, ..State::default() };
+                return Some(Transition::Closed);
+            }
+            state.trial = None;
+            state.opened_at = Some(Instant::now());
+            return None;
+        }
+        
+        state.outcomes.push_back(!ok);
+        if !ok {
+            state.failures += 1;
+        }
+        if state.outcomes.len() > self.window_size && state.outcomes.pop_front() == Some(true) {
+            state.failures -= 1;
+        }
+        let calls = state.outcomes.len();
+        if calls >= MIN_CALLS.min(self.window_size)
+            && state.failures as f32 / calls as f32 > self.fail_rate
+        {
+            state.opened_at = Some(Instant::now());
+            return Some(Transition::Opened);
+        }
+        None
+    }
+    
+    fn trial_due(&self, state: &State) -> bool {
+        match state.opened_at {
+            None => true,
+            Some(at) => at.elapsed() >= self.cooldown
+                && state.trial.is_none_or(|(_, t)| t.elapsed() >= self.cooldown),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn open_breaker(cooldown_ms: u64) -> (CircuitBreaker, Vec<Ticket>) {
+        let breaker = CircuitBreaker::new(&BreakerConfig { fail_rate: 0.5, window_size: 10, open_cooldown_ms: cooldown_ms });
+        let tickets: Vec<Ticket> = (0..12).map(|_| breaker.allow().unwrap()).collect();
+        for ticket in &tickets[..10] {
+            breaker.record(*ticket, false);
+        }
+        (breaker, tickets)
+    }
+    
+    #[test]
+    fn late_reports_do_not_close_an_open_breaker() {
+        let (breaker, late) = open_breaker(50);
+        std::thread::sleep(Duration::from_millis(60));
+        let trial = breaker.allow().unwrap();
+        assert!(breaker.allow().is_none(), "one trial at a time");
+        
+        // Calls let through before it opened finish during the trial
+        assert_eq!(breaker.record(late[10], true), None);
+        assert_eq!(breaker.record(late[11], true), None);
+        assert!(breaker.allow().is_none());
+        
+        assert_eq!(breaker.record(trial, true), Some(Transition::Closed));
+        assert!(breaker.allow().is_some());
+    }
+    
+    #[test]
+    fn a_failed_trial_opens_the_breaker_again() {
+        let (breaker, _) = open_breaker(50);
+        assert!(breaker.is_open());
+        assert!(breaker.allow().is_none());
+        std::thread::sleep(Duration::from_millis(60));
+        
+        let trial = breaker.allow().unwrap();
+        assert_eq!(breaker.record(trial, false), None);
+        assert!(breaker.allow().is_none(), "cooldown starts over");
+        
+        // The spent ticket cannot close it later either
+        std::thread::sleep(Duration::from_millis(60));
+        assert_eq!(breaker.record(trial, true), None);
+        let trial = breaker.allow().unwrap();
+        assert_eq!(breaker.record(trial, true), Some(Transition::Closed));
+    }
+}
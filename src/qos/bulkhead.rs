@@ -0,0 +1,448 @@
+use crate::config::Priority;
+use crate::errors::{LLMPoolError, Result};
+use crate::metrics::metrics;
+use crate::providers::{ChatMessage, InferParams, Provider, ProviderResponse};
+use async_trait::async_trait;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tracing::warn;
+
+/// Caps concurrent calls to one provider. Callers beyond the cap wait in a
+/// bounded queue; a full queue or a wait longer than the caller's deadline
+/// sheds the call with `Overloaded` instead of slowing everyone down. So
+/// does a wait that, going by how long calls have been holding their slot,
+/// would not end before the caller's deadline.
+///
+/// Waiters are served by priority class. Within a class, tenants share the
+/// provider by weighted fair queuing: each call is tagged with a virtual
+/// finish time that grows with its estimated cost divided by the tenant's
+/// weight, and the lowest tag goes next (earlier deadline on ties). Under
+/// pressure batch work goes first: it never takes the last free slot, only
+/// queues while the queue is under half full, and is displaced from a full
+/// queue by higher-priority calls.
+pub struct Bulkhead {
+    provider: String,
+    max_concurrency: usize,
+    max_queue: usize,
+    state: Mutex<State>,
+}
+
+struct State {
+    in_flight: usize,
+    next_id: u64,
+    waiting: Vec<Waiter>,
+    /// Finish tag of the last call sent to the provider
+    virtual_time: f64,
+    /// Finish tag of each tenant's latest call
+    last_finish: HashMap<String, f64>,
+    /// Moving average of how long a call holds its slot
+    hold_ms: f64,
+}
+
+/// Weight of the newest hold time in `State::hold_ms`
+const HOLD_ALPHA: f64 = 0.2;
+
+/// What the scheduler needs to know about one provider call.
+pub struct Call<'a> {
+    pub priority: Priority,
+    pub tenant: &'a str,
+    pub weight: f32,
+    /// Estimated work, in tokens
+    pub cost: f64,
+}
+
+struct Waiter {
+    id: u64,
+    priority: Priority,
+    tenant: String,
+    finish: f64,
+    /// What the tag charged the tenant, refunded if the call is never served
+    charge: f64,
+    deadline: Instant,
+    /// Sent `true` when handed a slot, `false` when displaced
+    tx: oneshot::Sender<bool>,
+}
+
+impl Waiter {
+    /// Lower sorts first: higher class, then fair share, then deadline.
+    fn rank(&self) -> (Priority, f64, Instant) {
+        (self.priority, self.finish, self.deadline)
+    }
+}
+
+/// A slot in the bulkhead, handed to the next waiter when dropped.
+pub struct Permit<'a> {
+    bulkhead: &'a Bulkhead,
+    granted: Instant,
+}
+
+impl Drop for Permit<'_> {
+    fn drop(&mut self) {
+        self.bulkhead.release(self.granted.elapsed());
+    }
+}
+
+/// A call waiting in the queue. If the caller goes away (client gone, an
+/// outer timeout), dropping it takes the waiter out of the queue, or hands
+/// on a slot it was given but never claimed.
+struct Queued<'a> {
+    bulkhead: &'a Bulkhead,
+    id: u64,
+    rx: oneshot::Receiver<bool>,
+}
+
+impl Drop for Queued<'_> {
+    fn drop(&mut self) {
+        let mut state = self.bulkhead.state.lock().unwrap();
+        if let Some(pos) = state.waiting.iter().position(|w| w.id == self.id) {
+            let waiter = state.remove(pos);
+            state.refund(&waiter.tenant, waiter.charge);
+        } else if matches!(self.rx.try_recv(), Ok(true)) {
+            state.in_flight -= 1;
+            self.bulkhead.hand_over(&mut state);
+        }
+    }
+}
+
+impl State {
+    /// Tags a call and charges it to its tenant.
+    fn tag(&mut self, call: &Call) -> f64 {
+        let start = self.last_finish.get(call.tenant)
+            .copied()
+            .unwrap_or(0.0)
+            .max(self.virtual_time);
+        let finish = start + charge(call);
+        self.last_finish.insert(call.tenant.to_string(), finish);
+        finish
+    }
+    
+    /// Gives back the share a waiter was charged when it leaves unserved,
+    /// so shed calls do not push the tenant's later ones back.
+    fn refund(&mut self, tenant: &str, charge: f64) {
+        let now = self.virtual_time;
+        if let Some(finish) = self.last_finish.get_mut(tenant) {
+            *finish = (*finish - charge).max(now);
+        }
+    }
+    
+    fn dispatched(&mut self, finish: f64) {
+        if finish > self.virtual_time {
+            self.virtual_time = finish;
+            // Tenants whose tag fell behind start again from virtual time
+            let now = self.virtual_time;
+            self.last_finish.retain(|_, f| *f > now);
+        }
+    }
+    
+    /// Expected wait for a new call of `priority`: the waiters it queues
+    /// behind, plus itself, served `max_concurrency` at a time.
+    fn expected_wait(&self, priority: Priority, max_concurrency: usize) -> Duration {
+        let ahead = self.waiting.iter().filter(|w| w.priority <= priority).count();
+        let rounds = (ahead + 1) as f64 / max_concurrency.max(1) as f64;
+        Duration::from_secs_f64(rounds * self.hold_ms / 1000.0)
+    }
+    
+    fn remove(&mut self, pos: usize) -> Waiter {
+        let waiter = self.waiting.swap_remove(pos);
+        metrics().tenant_queue_depth.with_label_values(&[&waiter.tenant]).dec();
+        waiter
+    }
+}
+
+impl Bulkhead {
+    pub fn new(provider: &str, max_concurrency: usize, max_queue: usize) -> Self {
+        Self {
+            provider: provider.to_string(),
+            max_concurrency,
+            max_queue,
+            state: Mutex::new(State {
+                in_flight: 0,
+                next_id: 0,
+                waiting: Vec::new(),
+                virtual_time: 0.0,
+                last_finish: HashMap::new(),
+                hold_ms: 0.0,
+            }),
+        }
+    }
+    
+    pub async fn acquire(&self, call: &Call<'_>, max_wait: Duration) -> Result<Permit<'_>> {
+        let (id, rx) = {
+            let mut state = self.state.lock().unwrap();
+            if self.admits(call.priority, state.in_flight) {
+                let finish = state.tag(call);
+                state.in_flight += 1;
+                state.dispatched(finish);
+                return Ok(Permit { bulkhead: self, granted: Instant::now() });
+            }
+            
+            let expected = state.expected_wait(call.priority, self.max_concurrency);
+            if expected > max_wait {
+                let reason = format!("expected wait {}ms exceeds deadline {}ms",
+                    expected.as_millis(), max_wait.as_millis());
+                return Err(self.shed(call, reason));
+            }
+            
+            let limit = match call.priority {
+                Priority::Batch => self.max_queue / 2,
+                _ => self.max_queue,
+            };
+            if state.waiting.len() >= limit && !self.displace(&mut state, call.priority) {
+                let reason = format!("queue full ({} waiting)", state.waiting.len());
+                return Err(self.shed(call, reason));
+            }
+            
+            let id = state.next_id;
+            state.next_id += 1;
+            let (tx, rx) = oneshot::channel();
+            let finish = state.tag(call);
+            state.waiting.push(Waiter {
+                id,
+                priority: call.priority,
+                tenant: call.tenant.to_string(),
+                finish,
+                charge: charge(call),
+                deadline: deadline_after(max_wait),
+                tx,
+            });
+            metrics().tenant_queue_depth.with_label_values(&[call.tenant]).inc();
+            (id, rx)
+        };
+        
+        let mut queued = Queued { bulkhead: self, id, rx };
+        let granted = match tokio::time::timeout(max_wait, &mut queued.rx).await {
+            Ok(result) => result.unwrap_or(false),
+            // Handed a slot or displaced just as the wait ran out; otherwise
+            // dropping `queued` leaves the queue
+            Err(_) => match queued.rx.try_recv() {
+                Ok(granted) => granted,
+                Err(_) => return Err(self.shed(call, format!("no slot within {}ms", max_wait.as_millis()))),
+            },
+        };
+        
+        if granted {
+            Ok(Permit { bulkhead: self, granted: Instant::now() })
+        } else {
+            Err(self.shed(call, "displaced by higher-priority calls".to_string()))
+        }
+    }
+    
+    /// Batch calls leave the last slot free for interactive traffic.
+    fn admits(&self, priority: Priority, in_flight: usize) -> bool {
+        match priority {
+            Priority::Batch if self.max_concurrency > 1 => in_flight < self.max_concurrency - 1,
+            _ => in_flight < self.max_concurrency,
+        }
+    }
+    
+    /// Makes room in a full queue by dropping the lowest-ranked waiter of a
+    /// class below `priority`, if there is one.
+    fn displace(&self, state: &mut State, priority: Priority) -> bool {
+        let victim = state.waiting.iter()
+            .enumerate()
+            .filter(|(_, w)| w.priority > priority)
+            .max_by(|(_, a), (_, b)| a.rank().partial_cmp(&b.rank()).unwrap())
+            .map(|(pos, _)| pos);
+        match victim {
+            Some(pos) => {
+                let waiter = state.remove(pos);
+                state.refund(&waiter.tenant, waiter.charge);
+                let _ = waiter.tx.send(false);
+                true
+            }
+            None => false,
+        }
+    }
+    
+    fn release(&self, held: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        let held_ms = held.as_secs_f64() * 1000.0;
+        state.hold_ms = if state.hold_ms == 0.0 {
+            held_ms
+        } else {
+            state.hold_ms + HOLD_ALPHA * (held_ms - state.hold_ms)
+        };
+        self.hand_over(&mut state);
+    }
+    
+    /// Gives free slots to the best waiters; ones that gave up have dropped
+    /// their receiver and are skipped.
+    fn hand_over(&self, state: &mut State) {
+        while let Some(pos) = state.waiting.iter()
+            .enumerate()
+            .min_by(|(_, a), (_, b)| a.rank().partial_cmp(&b.rank()).unwrap())
+            .map(|(pos, _)| pos)
+        {
+            if !self.admits(state.waiting[pos].priority, state.in_flight) {
+                return;
+            }
+            let Waiter { tenant, finish, charge, tx, .. } = state.remove(pos);
+            match tx.send(true) {
+                Ok(()) => {
+                    state.in_flight += 1;
+                    state.dispatched(finish);
+                    return;
+                }
+                Err(_) => state.refund(&tenant, charge),
+            }
+        }
+    }
+    
+    fn shed(&self, call: &Call, reason: String) -> LLMPoolError {
+        warn!("🚧 Shedding {} call from tenant '{}' to {}: {}",
+            call.priority.as_str(), call.tenant, self.provider, reason);
+        metrics().requests_shed
+            .with_label_values(&[&self.provider, call.priority.as_str(), call.tenant])
+            .inc();
+        LLMPoolError::Overloaded(format!("{}: {}", self.provider, reason))
+    }
+}
+
+/// Provider wrapper that runs generation and embedding calls through a
+/// bulkhead. Health checks, warm-up and residency probes bypass it.
+pub struct Bulkheaded {
+    inner: Arc<dyn Provider>,
+    bulkhead: Bulkhead,
+}
+
+impl Bulkheaded {
+    pub fn new(inner: Arc<dyn Provider>, max_concurrency: usize, max_queue: usize) -> Self {
+        let bulkhead = Bulkhead::new(inner.name(), max_concurrency, max_queue);
+        Self { inner, bulkhead }
+    }
+    
+    async fn enter(&self, call: Call<'_>, max_wait: Duration) -> Result<(Permit<'_>, i32)> {
+        let start = Instant::now();
+        let permit = self.bulkhead.acquire(&call, max_wait).await?;
+        let waited = start.elapsed();
+        metrics().queue_wait
+            .with_label_values(&[self.inner.name(), call.priority.as_str()])
+            .observe(waited.as_secs_f64());
+        metrics().tenant_queue_wait
+            .with_label_values(&[call.tenant])
+            .observe(waited.as_secs_f64());
+        Ok((permit, waited.as_millis() as i32))
+    }
+}
+
+#[async_trait]
+impl Provider for Bulkheaded {
+    fn name(&self) -> &str {
+        self.inner.name()
+    }
+    
+    fn supports(&self, task: &str) -> bool {
+        self.inner.supports(task)
+    }
+    
+    async fn infer(&self, prompt: &str, params: &InferParams) -> Result<ProviderResponse> {
+        let call = call_for(params, prompt.len());
+        let (_permit, queue_ms) = self.enter(call, max_wait(params)).await?;
+        let response = self.inner.infer(prompt, &remaining(params, queue_ms)?).await?;
+        Ok(ProviderResponse { queue_ms, ..response })
+    }
+    
+    async fn chat(&self, messages: &[ChatMessage], params: &InferParams) -> Result<ProviderResponse> {
+        let call = call_for(params, messages.iter().map(|m| m.content.len()).sum());
+        let (_permit, queue_ms) = self.enter(call, max_wait(params)).await?;
+        let response = self.inner.chat(messages, &remaining(params, queue_ms)?).await?;
+        Ok(ProviderResponse { queue_ms, ..response })
+    }
+    
+    async fn embed(&self, inputs: &[String], params: &InferParams) -> Result<Vec<Vec<f32>>> {
+        let call = call_for(params, inputs.iter().map(|i| i.len()).sum());
+        let (_permit, queue_ms) = self.enter(call, max_wait(params)).await?;
+        self.inner.embed(inputs, &remaining(params, queue_ms)?).await
+    }
+    
+    async fn warm_up(&self, timeout: Duration) -> Result<()> {
+        self.inner.warm_up(timeout).await
+    }
+    
+    async fn loaded_models(&self) -> Result<Vec<String>> {
+        self.inner.loaded_models().await
+    }
+    
+    async fn health(&self) -> bool {
+        self.inner.health().await
+    }
+}
+
+/// Prompt tokens (about four bytes each) plus the generation budget.
+fn call_for(params: &InferParams, prompt_bytes: usize) -> Call<'_> {
+    Call {
+        priority: params.priority,
+        tenant: &params.tenant_id,
+        weight: params.tenant_weight,
+        cost: prompt_bytes as f64 / 4.0 + params.max_tokens.max(0) as f64,
+    }
+}
+
+fn deadline_after(wait: Duration) -> Instant {
+    let now = Instant::now();
+    now.checked_add(wait).unwrap_or(now + Duration::from_secs(86400))
+}
+
+/// Virtual time a call adds to its tenant's share.
+fn charge(call: &Call) -> f64 {
+    call.cost / call.weight.max(0.01) as f64
+}
+
+fn max_wait(params: &InferParams) -> Duration {
+    Duration::from_millis(params.deadline_ms.max(0) as u64)
+}
+
+/// The caller's params with the time spent queuing taken off the deadline.
+/// Calls without a deadline (warm-up probes) pass as they are.
+fn remaining(params: &InferParams, queue_ms: i32) -> Result<InferParams> {
+    if params.deadline_ms <= 0 {
+        return Ok(params.clone());
+    }
+    let deadline_ms = params.deadline_ms - queue_ms;
+    if deadline_ms <= 0 {
+        return Err(LLMPoolError::DeadlineExceeded(params.deadline_ms));
+    }
+    Ok(InferParams { deadline_ms, ..params.clone() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn call(tenant: &str) -> Call<'_> {
+        Call { priority: Priority::Normal, tenant, weight: 1.0, cost: 10.0 }
+    }
+    
+    fn queued(bulkhead: &Bulkhead) -> usize {
+        bulkhead.state.lock().unwrap().waiting.len()
+    }
+    
+    #[tokio::test]
+    async fn dropped_waiters_leave_the_queue_and_return_their_slot() {
+        let bulkhead = Bulkhead::new("p", 1, 4);
+        let wait = Duration::from_secs(5);
+        let held = bulkhead.acquire(&call("a"), wait).await.unwrap();
+        
+        // Given up while queued, e.g. the client went away
+        let b = call("b");
+        let waiter = bulkhead.acquire(&b, wait);
+        assert!(tokio::time::timeout(Duration::from_millis(20), waiter).await.is_err());
+        assert_eq!(queued(&bulkhead), 0);
+        
+        // Given up after it was handed the slot but before it ran
+        let c = call("c");
+        let mut waiter = Box::pin(bulkhead.acquire(&c, wait));
+        assert!(tokio::time::timeout(Duration::from_millis(20), &mut waiter).await.is_err());
+        assert_eq!(queued(&bulkhead), 1);
+        drop(held);
+        assert_eq!(bulkhead.state.lock().unwrap().in_flight, 1);
+        drop(waiter);
+        assert_eq!(bulkhead.state.lock().unwrap().in_flight, 0);
+        
+        let next = tokio::time::timeout(Duration::from_millis(20), bulkhead.acquire(&call("d"), wait)).await;
+        assert!(next.unwrap().is_ok(), "the slot was handed on");
+    }
+}
@@ -1,6 +1,8 @@
 pub mod hedge;
 pub mod breaker;
+pub mod bulkhead;
 
 // QoS (Quality of Service) implementations
 // - Hedged requests: send duplicate requests after timeout
 // - Circuit breaker: prevent cascading failures
+// - Bulkhead: per-provider concurrency limit with a bounded queue
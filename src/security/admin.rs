@@ -0,0 +1,28 @@
+// Admin credential check for the /admin API
+use crate::config::AdminConfig;
+use crate::errors::{LLMPoolError, Result};
+
+pub const ADMIN_KEY_HEADER: &str = "x-admin-key";
+
+/// Accepts the request only if `presented` matches the configured admin key.
+/// The comparison runs in constant time over the full length of both keys.
+pub fn verify(config: &AdminConfig, presented: Option<&str>) -> Result<()> {
+    let Some(expected) = config.api_key.as_deref().filter(|k| !k.is_empty()) else {
+        return Err(LLMPoolError::AuthError("Admin API is disabled".to_string()));
+    };
+    let Some(presented) = presented else {
+        return Err(LLMPoolError::AuthError(format!("Missing {} header", ADMIN_KEY_HEADER)));
+    };
+    
+    let (a, b) = (expected.as_bytes(), presented.as_bytes());
+    let mut diff = a.len() ^ b.len();
+    for i in 0..a.len().max(b.len()) {
+        diff |= (a.get(i).copied().unwrap_or(0) ^ b.get(i).copied().unwrap_or(0)) as usize;
+    }
+    
+    if diff == 0 {
+        Ok(())
+    } else {
+        Err(LLMPoolError::AuthError("Invalid admin key".to_string()))
+    }
+}
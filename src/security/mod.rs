@@ -1,8 +1,10 @@
+pub mod admin;
 pub mod hmac;
 pub mod jwt;
 pub mod ratelimit;
 
 // Security implementations
+// - Admin API credential
 // - HMAC authentication
 // - JWT validation
 // - Rate limiting per tenant
@@ -0,0 +1,137 @@
+use super::http::{status_for, AppState, InferHttpRequest};
+use crate::cache::CacheFilter;
+use crate::errors::LLMPoolError;
+use crate::security::admin::{self as admin_auth, ADMIN_KEY_HEADER};
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::{self, Next},
+    response::{IntoResponse, Json, Response},
+    routing::{get, post},
+    Router,
+};
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+
+/// Cap on per-line errors echoed back by `/admin/cache/warm`
+const MAX_WARM_ERRORS: usize = 20;
+
+#[derive(Deserialize)]
+struct InvalidateRequest {
+    key: Option<String>,
+    task: Option<String>,
+    tenant_id: Option<String>,
+}
+
+/// One line of a warm-up file: a past request, optionally with its answer.
+/// Lines without `content` are run through the pool to produce one.
+#[derive(Deserialize)]
+struct WarmLine {
+    #[serde(flatten)]
+    request: InferHttpRequest,
+    content: Option<String>,
+    winner_model: Option<String>,
+}
+
+#[derive(Serialize, Default)]
+struct WarmReport {
+    stored: u64,
+    inferred: u64,
+    failed: u64,
+    errors: Vec<String>,
+}
+
+pub(super) fn router(state: AppState) -> Router<AppState> {
+    Router::new()
+        .route("/admin/cache/stats", get(stats_handler))
+        .route("/admin/cache/invalidate", post(invalidate_handler))
+        .route("/admin/cache/purge", post(purge_handler))
+        .route("/admin/cache/warm", post(warm_handler))
+        .route_layer(middleware::from_fn_with_state(state, require_admin))
+}
+
+async fn require_admin(
+    State(state): State<AppState>,
+    request: Request,
+    next: Next,
+) -> Result<Response, (StatusCode, String)> {
+    let presented = request
+        .headers()
+        .get(ADMIN_KEY_HEADER)
+        .and_then(|v| v.to_str().ok());
+    
+    if let Err(e) = admin_auth::verify(&state.config.admin, presented) {
+        warn!("🔒 Rejected admin request to {}: {}", request.uri().path(), e);
+        return Err((status_for(&e), e.to_string()));
+    }
+    
+    Ok(next.run(request).await)
+}
+
+async fn stats_handler(State(state): State<AppState>) -> impl IntoResponse {
+    Json(serde_json::json!({
+        "driver": state.config.cache.driver,
+        "ttl_seconds": state.config.cache.ttl_seconds,
+        "tasks": state.orchestrator.cache_stats(),
+    }))
+}
+
+async fn invalidate_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<InvalidateRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let filter = match (payload.key, payload.task, payload.tenant_id) {
+        (Some(key), None, None) => CacheFilter::Key(key),
+        (None, Some(task), None) => CacheFilter::Task(task),
+        (None, None, Some(tenant)) => CacheFilter::Tenant(tenant),
+        _ => {
+            let e = LLMPoolError::InvalidQuery(
+                "Exactly one of key, task or tenant_id is required".to_string()
+            );
+            return Err((status_for(&e), e.to_string()));
+        }
+    };
+    
+    let removed = state.orchestrator.invalidate_cache(&filter).await;
+    Ok(Json(serde_json::json!({ "removed": removed })))
+}
+
+async fn purge_handler(State(state): State<AppState>) -> impl IntoResponse {
+    let removed = state.orchestrator.invalidate_cache(&CacheFilter::All).await;
+    Json(serde_json::json!({ "removed": removed }))
+}
+
+/// Loads a JSONL body of past requests into the cache, one per line.
+async fn warm_handler(State(state): State<AppState>, body: String) -> impl IntoResponse {
+    let mut report = WarmReport::default();
+    
+    for (n, line) in body.lines().enumerate().filter(|(_, l)| !l.trim().is_empty()) {
+        let outcome = match serde_json::from_str::<WarmLine>(line) {
+            Ok(WarmLine { request, content: Some(content), winner_model }) => {
+                let model = winner_model.unwrap_or_else(|| "warmed".to_string());
+                state.orchestrator
+                    .warm_cache(request.into_infer_request(), &content, &model)
+                    .await
+                    .map(|_| report.stored += 1)
+            }
+            Ok(WarmLine { request, content: None, .. }) => {
+                state.orchestrator
+                    .infer(request.into_infer_request())
+                    .await
+                    .map(|_| report.inferred += 1)
+            }
+            Err(e) => Err(LLMPoolError::InvalidQuery(e.to_string())),
+        };
+        
+        if let Err(e) = outcome {
+            report.failed += 1;
+            if report.errors.len() < MAX_WARM_ERRORS {
+                report.errors.push(format!("line {}: {}", n + 1, e));
+            }
+        }
+    }
+    
+    info!("🔥 Cache warm-up: stored={}, inferred={}, failed={}",
+        report.stored, report.inferred, report.failed);
+    Json(report)
+}
@@ -1,7 +1,7 @@
-use crate::cache::Cache;
-use crate::config::Config;
-use crate::orchestrator::{InferRequest, Orchestrator};
-use crate::providers::ProviderPool;
+use crate::config::{Config, Priority, Sampling};
+use crate::orchestrator::{EmbedRequest as EmbedInput, InferRequest, Orchestrator};
+use crate::providers::{ChatMessage, ProviderPool};
+use base64::{engine::general_purpose::STANDARD as BASE64, Engine};
 use std::sync::Arc;
 use tonic::{transport::Server, Request, Response, Status};
 use tracing::info;
@@ -13,7 +13,8 @@ pub mod proto {
 
 use proto::{
     llm_pool_server::{LlmPool, LlmPoolServer},
-    Answer, EnsembleDecision, HealthRequest, HealthResponse, Query, Strategy as ProtoStrategy,
+    Answer, EmbedRequest, EmbedResponse, Embedding, EnsembleDecision, HealthRequest,
+    HealthResponse, Priority as ProtoPriority, Query, Strategy as ProtoStrategy,
     Task as ProtoTask,
 };
 
@@ -29,8 +30,14 @@ impl LlmPool for LLMPoolService {
         
         info!("📥 gRPC Infer request: {}", query.request_id);
         
-        let task_str = task_to_string(query.task());
-        let strategy_str = strategy_to_string(query.strategy());
+        // task_name wins over the enum so new tasks need no proto change
+        let task_str = if query.task_name.is_empty() {
+            task_to_string(query.task()).unwrap_or_default()
+        } else {
+            query.task_name.clone()
+        };
+        let strategy = strategy_to_string(query.strategy());
+        let priority = priority_from_proto(query.priority());
         
         let infer_req = InferRequest {
             request_id: query.request_id.clone(),
@@ -38,9 +45,17 @@ impl LlmPool for LLMPoolService {
             project_id: query.project_id,
             task: task_str,
             prompt: query.prompt,
+            messages: query.messages.into_iter()
+                .map(|m| ChatMessage { role: m.role, content: m.content })
+                .collect(),
+            input: (!query.input_json.is_empty()).then_some(query.input_json),
+            output_schema: (!query.output_schema_json.is_empty()).then_some(query.output_schema_json),
             max_tokens: query.max_tokens,
             deadline_ms: query.deadline_ms,
-            strategy: Some(strategy_str),
+            strategy,
+            sampling: query.sampling.map(sampling_from_proto).unwrap_or_default(),
+            images: query.images.iter().map(|bytes| BASE64.encode(bytes)).collect(),
+            priority,
         };
         
         let result = self.orchestrator.infer(infer_req).await?;
@@ -50,26 +65,59 @@ impl LlmPool for LLMPoolService {
             content: result.content,
             winner_model: result.winner_model,
             duration_ms: result.duration_ms,
+            queue_ms: result.queue_ms,
             from_cache: result.from_cache,
             decision: Some(EnsembleDecision {
                 strategy_used: string_to_strategy(&result.strategy_used) as i32,
                 models_queried: result.models_queried,
                 model_scores: vec![],
                 reason: "Ensemble decision".to_string(),
+                validation: result.validation.as_ref()
+                    .map(|v| v.outcome.as_str().to_string())
+                    .unwrap_or_default(),
+                reasks: result.validation.as_ref().map_or(0, |v| v.reasks as i32),
+                rejected: result.validation.map(|v| v.rejected).unwrap_or_default(),
             }),
-            meta: std::collections::HashMap::new(),
+            meta: result.meta,
         };
         
         Ok(Response::new(answer))
     }
     
+    async fn embed(&self, request: Request<EmbedRequest>) -> Result<Response<EmbedResponse>, Status> {
+        let req = request.into_inner();
+        
+        info!("📥 gRPC Embed request: {}", req.request_id);
+        
+        let result = self.orchestrator.embed(EmbedInput {
+            request_id: req.request_id,
+            tenant_id: req.tenant_id,
+            project_id: req.project_id,
+            inputs: req.inputs,
+            provider: (!req.provider.is_empty()).then_some(req.provider),
+            deadline_ms: req.deadline_ms,
+        }).await?;
+        
+        Ok(Response::new(EmbedResponse {
+            request_id: result.request_id,
+            provider: result.provider,
+            dimensions: result.embeddings.first().map_or(0, |v| v.len() as i32),
+            embeddings: result.embeddings.into_iter()
+                .map(|values| Embedding { values })
+                .collect(),
+            cached: result.cached as i32,
+            duration_ms: result.duration_ms,
+        }))
+    }
+    
     async fn health(
         &self,
         _request: Request<HealthRequest>,
     ) -> Result<Response<HealthResponse>, Status> {
         let health_status = self.providers.health_check().await;
         
-        let all_healthy = health_status.values().all(|&v| v);
+        let ready = self.providers.is_ready();
+        let all_healthy = ready && health_status.values().all(|&v| v);
         
         let provider_status: std::collections::HashMap<String, String> = health_status
             .into_iter()
@@ -80,14 +128,17 @@ impl LlmPool for LLMPoolService {
             healthy: all_healthy,
             provider_status,
             version: env!("CARGO_PKG_VERSION").to_string(),
+            ready,
+            warmup_status: self.providers.warmup_status(),
         }))
     }
 }
 
-pub async fn serve(config: Arc<Config>, providers: Arc<ProviderPool>) -> Result<(), Box<dyn std::error::Error>> {
-    let cache = Arc::new(Cache::new(config.cache.ttl_seconds, 10000));
-    let orchestrator = Arc::new(Orchestrator::new(config.clone(), providers.clone(), cache));
-    
+pub async fn serve(
+    config: Arc<Config>,
+    orchestrator: Arc<Orchestrator>,
+    providers: Arc<ProviderPool>,
+) -> Result<(), Box<dyn std::error::Error>> {
     let service = LLMPoolService {
         orchestrator,
         providers,
@@ -106,29 +157,51 @@ pub async fn serve(config: Arc<Config>, providers: Arc<ProviderPool>) -> Result<
     Ok(())
 }
 
-fn task_to_string(task: ProtoTask) -> String {
-    match task {
+fn task_to_string(task: ProtoTask) -> Option<String> {
+    let name = match task {
         ProtoTask::ExpandQueries => "expand_queries",
         ProtoTask::SiteTactics => "site_tactics",
         ProtoTask::RerankCandidates => "rerank_candidates",
         ProtoTask::Judge => "judge",
         ProtoTask::RecoveryPlan => "recovery_plan",
         ProtoTask::EnrichMetadata => "enrich_metadata",
-        _ => "unknown",
+        ProtoTask::Unspecified => return None,
+    };
+    Some(name.to_string())
+}
+
+fn sampling_from_proto(sampling: proto::Sampling) -> Sampling {
+    Sampling {
+        temperature: sampling.temperature,
+        top_p: sampling.top_p,
+        top_k: sampling.top_k,
+        seed: sampling.seed,
+        stop: sampling.stop,
+        repeat_penalty: sampling.repeat_penalty,
+        num_ctx: sampling.num_ctx,
     }
-    .to_string()
 }
 
-fn strategy_to_string(strategy: ProtoStrategy) -> String {
-    match strategy {
+/// `None` for STRATEGY_UNSPECIFIED so the task's strategy applies.
+fn strategy_to_string(strategy: ProtoStrategy) -> Option<String> {
+    let name = match strategy {
         ProtoStrategy::Fastest => "FASTEST",
         ProtoStrategy::Voting => "VOTING",
         ProtoStrategy::Weighted => "WEIGHTED",
         ProtoStrategy::Consensus => "CONSENSUS",
         ProtoStrategy::Judge => "JUDGE",
-        _ => "FASTEST",
+        ProtoStrategy::Unspecified => return None,
+    };
+    Some(name.to_string())
+}
+
+fn priority_from_proto(priority: ProtoPriority) -> Option<Priority> {
+    match priority {
+        ProtoPriority::Realtime => Some(Priority::Realtime),
+        ProtoPriority::Normal => Some(Priority::Normal),
+        ProtoPriority::Batch => Some(Priority::Batch),
+        ProtoPriority::Unspecified => None,
     }
-    .to_string()
 }
 
 fn string_to_strategy(s: &str) -> ProtoStrategy {
@@ -1,7 +1,10 @@
-use crate::cache::Cache;
-use crate::config::Config;
-use crate::orchestrator::{InferRequest, Orchestrator};
-use crate::providers::ProviderPool;
+use super::{admin, jobs};
+use crate::config::{Config, Priority, Sampling};
+use crate::errors::LLMPoolError;
+use crate::jobs::JobManager;
+use crate::metrics::metrics;
+use crate::orchestrator::{EmbedRequest, InferRequest, Orchestrator};
+use crate::providers::{ChatMessage, ProviderPool};
 use axum::{
     extract::State,
     http::StatusCode,
@@ -15,21 +18,60 @@ use tower_http::trace::TraceLayer;
 use tracing::info;
 
 #[derive(Clone)]
-struct AppState {
-    orchestrator: Arc<Orchestrator>,
-    providers: Arc<ProviderPool>,
+pub(super) struct AppState {
+    pub(super) config: Arc<Config>,
+    pub(super) orchestrator: Arc<Orchestrator>,
+    pub(super) providers: Arc<ProviderPool>,
+    /// None when `[jobs]` is disabled
+    pub(super) jobs: Option<Arc<JobManager>>,
 }
 
 #[derive(Deserialize)]
-struct InferHttpRequest {
+pub(super) struct InferHttpRequest {
     request_id: Option<String>,
     tenant_id: Option<String>,
     project_id: Option<String>,
     task: String,
+    #[serde(default)]
     prompt: String,
+    #[serde(default)]
+    messages: Vec<ChatMessage>,
+    /// Structured input rendered through the task's prompt template
+    input: Option<serde_json::Value>,
+    /// JSON Schema for the answer, overriding the task's schema
+    output_schema: Option<serde_json::Value>,
     max_tokens: Option<i32>,
     deadline_ms: Option<i32>,
     strategy: Option<String>,
+    /// temperature, top_p, top_k, seed, stop, repeat_penalty, num_ctx
+    #[serde(flatten)]
+    sampling: Sampling,
+    /// Base64-encoded images for vision models
+    #[serde(default)]
+    images: Vec<String>,
+    /// "realtime", "normal" or "batch"; defaults per tenant
+    priority: Option<Priority>,
+}
+
+impl InferHttpRequest {
+    pub(super) fn into_infer_request(self) -> InferRequest {
+        InferRequest {
+            request_id: self.request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+            tenant_id: self.tenant_id.unwrap_or_default(),
+            project_id: self.project_id.unwrap_or_default(),
+            task: self.task,
+            prompt: self.prompt,
+            messages: self.messages,
+            input: self.input.map(|v| v.to_string()),
+            output_schema: self.output_schema.map(|v| v.to_string()),
+            max_tokens: self.max_tokens.unwrap_or(0),
+            deadline_ms: self.deadline_ms.unwrap_or(0),
+            strategy: self.strategy,
+            sampling: self.sampling,
+            images: self.images,
+            priority: self.priority,
+        }
+    }
 }
 
 #[derive(Serialize)]
@@ -38,8 +80,41 @@ struct InferHttpResponse {
     content: String,
     winner_model: String,
     duration_ms: i32,
+    queue_ms: i32,
     from_cache: bool,
     strategy_used: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    validation: Option<ValidationHttp>,
+    #[serde(skip_serializing_if = "std::collections::HashMap::is_empty")]
+    meta: std::collections::HashMap<String, String>,
+}
+
+#[derive(Deserialize)]
+struct EmbedHttpRequest {
+    request_id: Option<String>,
+    tenant_id: Option<String>,
+    project_id: Option<String>,
+    inputs: Vec<String>,
+    provider: Option<String>,
+    deadline_ms: Option<i32>,
+}
+
+#[derive(Serialize)]
+struct EmbedHttpResponse {
+    request_id: String,
+    provider: String,
+    embeddings: Vec<Vec<f32>>,
+    dimensions: usize,
+    cached: usize,
+    duration_ms: i32,
+}
+
+#[derive(Serialize)]
+struct ValidationHttp {
+    outcome: &'static str,
+    reasks: u32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    rejected: Vec<String>,
 }
 
 #[derive(Serialize)]
@@ -47,42 +122,49 @@ struct HealthHttpResponse {
     healthy: bool,
     providers: std::collections::HashMap<String, String>,
     version: String,
+    ready: bool,
+    warmup: std::collections::HashMap<String, String>,
+    /// Per-endpoint state of providers with several endpoints
+    #[serde(skip_serializing_if = "std::collections::HashMap::is_empty")]
+    endpoints: std::collections::HashMap<String, std::collections::HashMap<String, String>>,
 }
 
 async fn health_handler(State(state): State<AppState>) -> impl IntoResponse {
     let health_status = state.providers.health_check().await;
-    let all_healthy = health_status.values().all(|&v| v);
+    let ready = state.providers.is_ready();
+    let all_healthy = ready && health_status.values().all(|&v| v);
     
     let providers: std::collections::HashMap<String, String> = health_status
         .into_iter()
         .map(|(k, v)| (k, if v { "healthy".to_string() } else { "unhealthy".to_string() }))
         .collect();
     
-    Json(HealthHttpResponse {
+    // 503 while models are still loading, so readiness probes hold traffic
+    let status = if ready { StatusCode::OK } else { StatusCode::SERVICE_UNAVAILABLE };
+    (status, Json(HealthHttpResponse {
         healthy: all_healthy,
         providers,
         version: env!("CARGO_PKG_VERSION").to_string(),
-    })
+        ready,
+        warmup: state.providers.warmup_status(),
+        endpoints: state.providers.endpoint_status(),
+    }))
+}
+
+async fn metrics_handler() -> impl IntoResponse {
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        metrics().render(),
+    )
 }
 
 async fn infer_handler(
     State(state): State<AppState>,
     Json(payload): Json<InferHttpRequest>,
 ) -> Result<Json<InferHttpResponse>, (StatusCode, String)> {
-    let request_id = payload.request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let infer_req = payload.into_infer_request();
     
-    info!("📥 HTTP Infer request: {}", request_id);
-    
-    let infer_req = InferRequest {
-        request_id: request_id.clone(),
-        tenant_id: payload.tenant_id.unwrap_or_default(),
-        project_id: payload.project_id.unwrap_or_default(),
-        task: payload.task,
-        prompt: payload.prompt,
-        max_tokens: payload.max_tokens.unwrap_or(256),
-        deadline_ms: payload.deadline_ms.unwrap_or(1500),
-        strategy: payload.strategy,
-    };
+    info!("📥 HTTP Infer request: {}", infer_req.request_id);
     
     match state.orchestrator.infer(infer_req).await {
         Ok(result) => Ok(Json(InferHttpResponse {
@@ -90,28 +172,80 @@ async fn infer_handler(
             content: result.content,
             winner_model: result.winner_model,
             duration_ms: result.duration_ms,
+            queue_ms: result.queue_ms,
             from_cache: result.from_cache,
             strategy_used: result.strategy_used,
+            validation: result.validation.map(|v| ValidationHttp {
+                outcome: v.outcome.as_str(),
+                reasks: v.reasks,
+                rejected: v.rejected,
+            }),
+            meta: result.meta,
         })),
-        Err(e) => Err((StatusCode::INTERNAL_SERVER_ERROR, e.to_string())),
+        Err(e) => Err((status_for(&e), e.to_string())),
+    }
+}
+
+async fn embed_handler(
+    State(state): State<AppState>,
+    Json(payload): Json<EmbedHttpRequest>,
+) -> Result<Json<EmbedHttpResponse>, (StatusCode, String)> {
+    let embed_req = EmbedRequest {
+        request_id: payload.request_id.unwrap_or_else(|| uuid::Uuid::new_v4().to_string()),
+        tenant_id: payload.tenant_id.unwrap_or_default(),
+        project_id: payload.project_id.unwrap_or_default(),
+        inputs: payload.inputs,
+        provider: payload.provider,
+        deadline_ms: payload.deadline_ms.unwrap_or(0),
+    };
+    
+    info!("📥 HTTP Embed request: {}", embed_req.request_id);
+    
+    match state.orchestrator.embed(embed_req).await {
+        Ok(result) => Ok(Json(EmbedHttpResponse {
+            request_id: result.request_id,
+            provider: result.provider,
+            dimensions: result.embeddings.first().map_or(0, Vec::len),
+            embeddings: result.embeddings,
+            cached: result.cached,
+            duration_ms: result.duration_ms,
+        })),
+        Err(e) => Err((status_for(&e), e.to_string())),
+    }
+}
+
+pub(super) fn status_for(err: &LLMPoolError) -> StatusCode {
+    match err {
+        LLMPoolError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+        LLMPoolError::AuthError(_) => StatusCode::UNAUTHORIZED,
+        LLMPoolError::Conflict(_) => StatusCode::CONFLICT,
+        LLMPoolError::RateLimitExceeded => StatusCode::TOO_MANY_REQUESTS,
+        LLMPoolError::DeadlineExceeded(_) => StatusCode::GATEWAY_TIMEOUT,
+        LLMPoolError::Overloaded(_) | LLMPoolError::ProviderUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::INTERNAL_SERVER_ERROR,
     }
 }
 
 pub async fn serve(
     config: Arc<Config>,
+    orchestrator: Arc<Orchestrator>,
     providers: Arc<ProviderPool>,
+    jobs: Option<Arc<JobManager>>,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    let cache = Arc::new(Cache::new(config.cache.ttl_seconds, 10000));
-    let orchestrator = Arc::new(Orchestrator::new(config.clone(), providers.clone(), cache));
-    
     let state = AppState {
+        config: config.clone(),
         orchestrator,
         providers,
+        jobs,
     };
     
     let app = Router::new()
         .route("/health", get(health_handler))
+        .route("/metrics", get(metrics_handler))
         .route("/v1/infer", post(infer_handler))
+        .route("/v1/embed", post(embed_handler))
+        .merge(jobs::router())
+        .merge(admin::router(state.clone()))
         .layer(TraceLayer::new_for_http())
         .with_state(state);
     
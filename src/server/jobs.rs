@@ -0,0 +1,106 @@
+use super::http::{status_for, AppState, InferHttpRequest};
+use crate::jobs::{JobManager, JobStatus};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json},
+    routing::{get, post},
+    Router,
+};
+use serde::Deserialize;
+use std::sync::Arc;
+use tracing::info;
+
+/// Job settings passed as query parameters, for JSONL bodies.
+#[derive(Deserialize, Default)]
+struct SubmitParams {
+    tenant_id: Option<String>,
+    deadline_ms: Option<i32>,
+    callback_url: Option<String>,
+}
+
+/// A JSON job body: the queries plus the same settings as `SubmitParams`.
+#[derive(Deserialize)]
+struct JobHttpRequest {
+    queries: Vec<InferHttpRequest>,
+    #[serde(flatten)]
+    params: SubmitParams,
+}
+
+pub(super) fn router() -> Router<AppState> {
+    Router::new()
+        .route("/v1/jobs", post(submit_handler))
+        .route("/v1/jobs/:id", get(status_handler))
+        .route("/v1/jobs/:id/results", get(results_handler))
+}
+
+fn manager(state: &AppState) -> Result<&Arc<JobManager>, (StatusCode, String)> {
+    state.jobs.as_ref()
+        .ok_or((StatusCode::NOT_FOUND, "Batch jobs are disabled".to_string()))
+}
+
+/// Takes a JSON array of queries, a `{"queries": [...]}` object, or one
+/// query per line (JSONL).
+async fn submit_handler(
+    State(state): State<AppState>,
+    Query(query_params): Query<SubmitParams>,
+    body: String,
+) -> Result<(StatusCode, Json<JobStatus>), (StatusCode, String)> {
+    let jobs = manager(&state)?;
+    let bad_request = |n: usize, e: serde_json::Error| {
+        (StatusCode::BAD_REQUEST, format!("query {}: {}", n + 1, e))
+    };
+    
+    let trimmed = body.trim_start();
+    let (queries, params) = if trimmed.starts_with('[') {
+        let queries = serde_json::from_str::<Vec<InferHttpRequest>>(trimmed).map_err(|e| bad_request(0, e))?;
+        (queries, query_params)
+    } else if let Ok(request) = serde_json::from_str::<JobHttpRequest>(trimmed) {
+        (request.queries, request.params)
+    } else {
+        let queries = body.lines()
+            .filter(|l| !l.trim().is_empty())
+            .enumerate()
+            .map(|(n, line)| serde_json::from_str::<InferHttpRequest>(line).map_err(|e| bad_request(n, e)))
+            .collect::<Result<Vec<_>, _>>()?;
+        (queries, query_params)
+    };
+    
+    let queries = queries.into_iter()
+        .map(|q| {
+            let mut request = q.into_infer_request();
+            if request.tenant_id.is_empty() {
+                request.tenant_id = params.tenant_id.clone().unwrap_or_default();
+            }
+            request
+        })
+        .collect();
+    
+    let status = jobs.submit(queries, params.deadline_ms, params.callback_url)
+        .await
+        .map_err(|e| (status_for(&e), e.to_string()))?;
+    info!("📥 HTTP batch job submitted: {}", status.id);
+    Ok((StatusCode::ACCEPTED, Json(status)))
+}
+
+async fn status_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<Json<JobStatus>, (StatusCode, String)> {
+    manager(&state)?
+        .status(&id)
+        .map(Json)
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown job: {}", id)))
+}
+
+/// Results so far, one JSON object per line in query order.
+async fn results_handler(
+    State(state): State<AppState>,
+    Path(id): Path<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    let body = manager(&state)?
+        .results(&id)
+        .await
+        .ok_or((StatusCode::NOT_FOUND, format!("Unknown job: {}", id)))?;
+    Ok(([(header::CONTENT_TYPE, "application/x-ndjson")], body))
+}
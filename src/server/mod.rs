@@ -1,3 +1,5 @@
+mod admin;
 pub mod grpc;
 pub mod http;
+mod jobs;
 pub mod router;
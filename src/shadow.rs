@@ -0,0 +1,238 @@
+use crate::config::{Config, Priority};
+use crate::metrics::metrics;
+use crate::providers::{InferParams, PromptInput, Provider, ProviderPool, ProviderResponse};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+/// Copies live traffic to candidate models after the client has its answer.
+///
+/// A shadow provider gets the same prompt and settings as the ensemble, at
+/// batch priority, for a sample of each task's fresh answers. Its output is
+/// only compared with the winner, counted in metrics and written to the
+/// evaluation log; it never reaches the client or the cache.
+pub struct ShadowTraffic {
+    by_task: HashMap<String, Vec<Shadow>>,
+    agreement_threshold: f64,
+    log: Option<Arc<tokio::sync::Mutex<tokio::fs::File>>>,
+}
+
+struct Shadow {
+    provider: Arc<dyn Provider>,
+    /// Share of the task's fresh answers mirrored, 0-100
+    sample_percent: f64,
+}
+
+/// What was asked and what the ensemble answered.
+pub struct Mirrored<'a> {
+    pub request_id: &'a str,
+    pub task: &'a str,
+    pub tenant_id: &'a str,
+    pub input: &'a PromptInput,
+    pub params: &'a InferParams,
+    pub winner: &'a ProviderResponse,
+}
+
+/// One line of the evaluation log.
+#[derive(Serialize)]
+struct Evaluation {
+    timestamp: chrono::DateTime<chrono::Utc>,
+    request_id: String,
+    task: String,
+    tenant_id: String,
+    provider: String,
+    /// "ok", "error" or "timeout"
+    outcome: &'static str,
+    duration_ms: i32,
+    winner_model: String,
+    winner_duration_ms: i32,
+    winner_content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    similarity: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    agreed: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    content: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl ShadowTraffic {
+    /// None when no provider has a `[providers.shadow]` block.
+    pub fn from_config(config: &Config, pool: &ProviderPool) -> Option<Self> {
+        let mut by_task: HashMap<String, Vec<Shadow>> = HashMap::new();
+        for pconfig in &config.providers {
+            let (Some(shadow), Some(provider)) = (&pconfig.shadow, pool.get(&pconfig.name)) else {
+                continue;
+            };
+            for task in &shadow.tasks {
+                // A provider that already serves the task would be compared
+                // with itself
+                if pool.providers_for_task(task).iter().any(|p| p.name() == pconfig.name) {
+                    warn!("👥 {} serves {} and cannot shadow it", pconfig.name, task);
+                    continue;
+                }
+                by_task.entry(task.clone())
+                    .or_default()
+                    .push(Shadow {
+                        provider: provider.clone(),
+                        sample_percent: shadow.sample_percent.clamp(0.0, 100.0),
+                    });
+            }
+        }
+        if by_task.is_empty() {
+            return None;
+        }
+        
+        let log = config.shadow.log_path.as_ref().and_then(|path| {
+            match std::fs::OpenOptions::new().create(true).append(true).open(path) {
+                Ok(file) => Some(Arc::new(tokio::sync::Mutex::new(tokio::fs::File::from_std(file)))),
+                Err(e) => {
+                    warn!("👥 Cannot open shadow log {}: {}", path, e);
+                    None
+                }
+            }
+        });
+        
+        let providers: HashSet<&str> = by_task.values().flatten().map(|s| s.provider.name()).collect();
+        info!("👥 Shadow traffic: {} providers on {} tasks", providers.len(), by_task.len());
+        Some(Self {
+            by_task,
+            agreement_threshold: config.shadow.agreement_threshold,
+            log,
+        })
+    }
+    
+    /// Sends the request to the task's sampled shadow providers in the
+    /// background and returns at once.
+    pub fn mirror(&self, call: Mirrored<'_>) {
+        let Some(shadows) = self.by_task.get(call.task) else {
+            return;
+        };
+        
+        for shadow in shadows {
+            if !sampled(call.request_id, shadow.provider.name(), shadow.sample_percent) {
+                continue;
+            }
+            let provider = shadow.provider.clone();
+            let input = call.input.clone();
+            let params = InferParams { priority: Priority::Batch, ..call.params.clone() };
+            let mut evaluation = Evaluation {
+                timestamp: chrono::Utc::now(),
+                request_id: call.request_id.to_string(),
+                task: call.task.to_string(),
+                tenant_id: call.tenant_id.to_string(),
+                provider: provider.name().to_string(),
+                outcome: "ok",
+                duration_ms: 0,
+                winner_model: call.winner.model.clone(),
+                winner_duration_ms: call.winner.duration_ms,
+                winner_content: call.winner.content.clone(),
+                similarity: None,
+                agreed: None,
+                content: None,
+                error: None,
+            };
+            let threshold = self.agreement_threshold;
+            let log = self.log.clone();
+            
+            tokio::spawn(async move {
+                // Held to the same deadline the ensemble had
+                let deadline = Duration::from_millis(params.deadline_ms.max(1) as u64);
+                let start = Instant::now();
+                let result = tokio::time::timeout(deadline, input.send(provider.as_ref(), &params)).await;
+                evaluation.duration_ms = start.elapsed().as_millis() as i32;
+                
+                match result {
+                    Ok(Ok(response)) => {
+                        let similarity = similarity(&response.content, &evaluation.winner_content);
+                        evaluation.similarity = Some(similarity);
+                        evaluation.agreed = Some(similarity >= threshold);
+                        evaluation.content = Some(response.content);
+                    }
+                    Ok(Err(e)) => {
+                        evaluation.outcome = "error";
+                        evaluation.error = Some(e.to_string());
+                    }
+                    Err(_) => evaluation.outcome = "timeout",
+                }
+                record(&evaluation);
+                if let Some(log) = log {
+                    write_log(&log, &evaluation).await;
+                }
+            });
+        }
+    }
+}
+
+fn record(evaluation: &Evaluation) {
+    let labels = [evaluation.provider.as_str(), evaluation.task.as_str()];
+    metrics().shadow_requests
+        .with_label_values(&[labels[0], labels[1], evaluation.outcome])
+        .inc();
+    metrics().shadow_latency
+        .with_label_values(&labels)
+        .observe(evaluation.duration_ms as f64 / 1000.0);
+    if let (Some(similarity), Some(agreed)) = (evaluation.similarity, evaluation.agreed) {
+        metrics().shadow_similarity.with_label_values(&labels).observe(similarity);
+        metrics().shadow_agreement
+            .with_label_values(&[labels[0], labels[1], if agreed { "true" } else { "false" }])
+            .inc();
+    }
+}
+
+async fn write_log(log: &tokio::sync::Mutex<tokio::fs::File>, evaluation: &Evaluation) {
+    let Ok(mut line) = serde_json::to_string(evaluation) else {
+        return;
+    };
+    line.push('\n');
+    let mut file = log.lock().await;
+    if let Err(e) = async { file.write_all(line.as_bytes()).await?; file.flush().await }.await {
+        warn!("👥 Cannot write shadow log: {}", e);
+    }
+}
+
+/// Stable per request and provider, so a retried request is mirrored (or
+/// not) the same way.
+fn sampled(request_id: &str, provider: &str, sample_percent: f64) -> bool {
+    if sample_percent >= 100.0 {
+        return true;
+    }
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    request_id.hash(&mut hasher);
+    provider.hash(&mut hasher);
+    ((hasher.finish() % 10_000) as f64) < sample_percent * 100.0
+}
+
+/// 1.0 for equal answers (as JSON values when both parse), otherwise the
+/// Jaccard overlap of their lowercase word sets.
+fn similarity(a: &str, b: &str) -> f64 {
+    if a.trim() == b.trim() {
+        return 1.0;
+    }
+    if let (Ok(x), Ok(y)) = (
+        serde_json::from_str::<serde_json::Value>(a),
+        serde_json::from_str::<serde_json::Value>(b),
+    ) {
+        if x == y {
+            return 1.0;
+        }
+    }
+    
+    let words = |s: &str| -> HashSet<String> {
+        s.split(|c: char| !c.is_alphanumeric())
+            .filter(|w| !w.is_empty())
+            .map(str::to_lowercase)
+            .collect()
+    };
+    let (a, b) = (words(a), words(b));
+    let union = a.union(&b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(&b).count() as f64 / union as f64
+}
@@ -0,0 +1,92 @@
+use crate::errors::{LLMPoolError, Result};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::watch;
+
+type Flight<T> = watch::Receiver<Option<Result<T>>>;
+
+/// Collapses concurrent calls with the same key into one execution.
+///
+/// The first caller (the leader) runs the work; everyone arriving while it
+/// is in flight waits and receives a clone of the same result. Nothing is
+/// kept once the flight lands, so this is not a cache.
+pub struct SingleFlight<T> {
+    flights: Mutex<HashMap<String, Flight<T>>>,
+}
+
+impl<T: Clone> SingleFlight<T> {
+    pub fn new() -> Self {
+        Self {
+            flights: Mutex::new(HashMap::new()),
+        }
+    }
+    
+    /// Returns the result and whether it was shared from another caller.
+    /// `work` is only polled when this caller ends up leading the flight.
+    /// A caller that joins someone else's flight waits at most `patience`,
+    /// then gets `DeadlineExceeded`.
+    pub async fn run<F>(&self, key: String, work: F, patience: Duration) -> (Result<T>, bool)
+    where
+        F: Future<Output = Result<T>>,
+    {
+        let mut work = Some(work);
+        
+        loop {
+            let joined = {
+                let mut flights = self.flights.lock().unwrap();
+                match flights.get(&key) {
+                    Some(flight) => Err(flight.clone()),
+                    None => {
+                        let (tx, rx) = watch::channel(None);
+                        flights.insert(key.clone(), rx.clone());
+                        Ok((tx, rx))
+                    }
+                }
+            };
+            
+            match joined {
+                Ok((tx, rx)) => {
+                    let _landing = Landing { owner: self, key: &key, flight: rx };
+                    let result = work.take().expect("leader runs once").await;
+                    tx.send_replace(Some(result.clone()));
+                    return (result, false);
+                }
+                Err(mut flight) => {
+                    let landed = tokio::time::timeout(patience, flight.wait_for(Option::is_some)).await;
+                    match landed {
+                        Ok(Ok(done)) => {
+                            if let Some(result) = done.clone() {
+                                return (result, true);
+                            }
+                        }
+                        Ok(Err(_)) => {}
+                        Err(_) => {
+                            let ms = patience.as_millis() as i32;
+                            return (Err(LLMPoolError::DeadlineExceeded(ms)), true);
+                        }
+                    }
+                    // The leader was cancelled before finishing; take over
+                }
+            }
+        }
+    }
+}
+
+/// Removes the leader's flight on completion or cancellation, unless a
+/// newer flight already took the key.
+struct Landing<'a, T> {
+    owner: &'a SingleFlight<T>,
+    key: &'a str,
+    flight: Flight<T>,
+}
+
+impl<T> Drop for Landing<'_, T> {
+    fn drop(&mut self) {
+        let mut flights = self.owner.flights.lock().unwrap();
+        if flights.get(self.key).is_some_and(|f| f.same_channel(&self.flight)) {
+            flights.remove(self.key);
+        }
+    }
+}
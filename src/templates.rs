@@ -0,0 +1,165 @@
+use crate::config::{TaskConfig, TemplatesConfig};
+use crate::errors::{LLMPoolError, Result};
+use notify::{Event, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use tracing::{error, info, warn};
+
+/// Placeholder replaced by the request input. Templates without it get the
+/// input appended as a trailing JSON block instead.
+const INPUT_PLACEHOLDER: &str = "{{input}}";
+
+#[derive(Debug, Clone)]
+pub struct Template {
+    pub name: String,
+    /// First 12 hex chars of the SHA-256 of the file, so any edit is visible
+    pub version: String,
+    body: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedPrompt {
+    pub prompt: String,
+    pub template: String,
+    pub version: String,
+}
+
+impl Template {
+    fn new(name: String, body: String) -> Self {
+        use sha2::{Sha256, Digest};
+        let digest = format!("{:x}", Sha256::digest(body.as_bytes()));
+        Self {
+            name,
+            version: digest[..12].to_string(),
+            body,
+        }
+    }
+    
+    fn render(&self, input: &str) -> String {
+        if self.body.contains(INPUT_PLACEHOLDER) {
+            return self.body.replace(INPUT_PLACEHOLDER, input);
+        }
+        format!(
+            "{}\n\n## Entrada desta requisição (JSON)\n```json\n{}\n```\n",
+            self.body.trim_end(),
+            input
+        )
+    }
+}
+
+/// Task prompt templates loaded from `prompts/*.md`, keyed by file stem.
+pub struct TemplateRegistry {
+    dir: PathBuf,
+    by_task: HashMap<String, String>,
+    templates: RwLock<HashMap<String, Template>>,
+}
+
+impl TemplateRegistry {
+    /// `[tasks.<name>] template` takes precedence over `[templates.by_task]`.
+    pub fn load(config: &TemplatesConfig, tasks: &HashMap<String, TaskConfig>) -> Result<Self> {
+        let mut by_task = config.by_task.clone();
+        for (task, task_config) in tasks {
+            if let Some(template) = &task_config.template {
+                by_task.insert(task.clone(), template.clone());
+            }
+        }
+        
+        let registry = Self {
+            dir: PathBuf::from(&config.dir),
+            by_task,
+            templates: RwLock::new(HashMap::new()),
+        };
+        // Deployments from before templates existed have no prompts/ yet;
+        // they only lose `input` rendering, not startup
+        if !registry.dir.is_dir() {
+            warn!("⚠️  Templates dir {:?} not found, requests with `input` will be rejected", registry.dir);
+            return Ok(registry);
+        }
+        registry.reload()?;
+        Ok(registry)
+    }
+    
+    /// Re-reads every template; the previous set is kept if the directory
+    /// cannot be read.
+    pub fn reload(&self) -> Result<()> {
+        let entries = std::fs::read_dir(&self.dir).map_err(|e| {
+            LLMPoolError::ConfigError(format!("Failed to read templates dir {:?}: {}", self.dir, e))
+        })?;
+        
+        let mut templates = HashMap::new();
+        for path in entries.filter_map(|e| e.ok()).map(|e| e.path()) {
+            if path.extension().and_then(|e| e.to_str()) != Some("md") {
+                continue;
+            }
+            let Some(name) = path.file_stem().and_then(|s| s.to_str()) else {
+                continue;
+            };
+            match std::fs::read_to_string(&path) {
+                Ok(body) => {
+                    templates.insert(name.to_string(), Template::new(name.to_string(), body));
+                }
+                Err(e) => warn!("Skipping template {:?}: {}", path, e),
+            }
+        }
+        
+        info!("📝 Loaded {} prompt templates from {:?}", templates.len(), self.dir);
+        *self.templates.write().unwrap() = templates;
+        Ok(())
+    }
+    
+    /// Template name for a task: the `by_task` override, else the task itself.
+    fn name_for<'a>(&'a self, task: &'a str) -> &'a str {
+        self.by_task.get(task).map(String::as_str).unwrap_or(task)
+    }
+    
+    pub fn render(&self, task: &str, input: &str) -> Result<RenderedPrompt> {
+        let name = self.name_for(task);
+        let templates = self.templates.read().unwrap();
+        let template = templates.get(name).ok_or_else(|| {
+            LLMPoolError::InvalidQuery(format!("No prompt template for task: {}", task))
+        })?;
+        
+        Ok(RenderedPrompt {
+            prompt: template.render(input),
+            template: template.name.clone(),
+            version: template.version.clone(),
+        })
+    }
+}
+
+/// Reloads the registry whenever a file in its directory changes.
+pub fn watch(registry: Arc<TemplateRegistry>) -> Result<tokio::task::JoinHandle<()>> {
+    let dir = registry.dir.clone();
+    
+    let handle = tokio::spawn(async move {
+        // `load` already warned about a missing directory
+        if !dir.is_dir() {
+            return;
+        }
+        
+        let (tx, mut rx) = tokio::sync::mpsc::channel(10);
+        
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            if let Ok(event) = res {
+                let _ = tx.blocking_send(event);
+            }
+        }).expect("Failed to create watcher");
+        
+        if let Err(e) = watcher.watch(Path::new(&dir), RecursiveMode::NonRecursive) {
+            error!("❌ Failed to watch templates dir {:?}: {}", dir, e);
+            return;
+        }
+        
+        info!("👀 Watching prompt templates for changes: {:?}", dir);
+        
+        while let Some(_event) = rx.recv().await {
+            match registry.reload() {
+                Ok(()) => info!("♻️  Prompt templates reloaded"),
+                Err(e) => error!("❌ Failed to reload prompt templates: {}", e),
+            }
+        }
+    });
+    
+    Ok(handle)
+}
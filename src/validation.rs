@@ -0,0 +1,159 @@
+use crate::config::{TaskConfig, ValidationConfig};
+use crate::errors::{LLMPoolError, Result};
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tracing::info;
+
+/// Cap on schema errors quoted back to the model and the caller
+const MAX_REPORTED_ERRORS: usize = 3;
+
+/// How a candidate's output met the task schema.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValidationOutcome {
+    /// Valid JSON as returned
+    Valid,
+    /// Valid after stripping code fences or surrounding prose
+    Repaired,
+    /// Valid only after re-asking with the validation error
+    Reasked,
+}
+
+impl ValidationOutcome {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ValidationOutcome::Valid => "valid",
+            ValidationOutcome::Repaired => "repaired",
+            ValidationOutcome::Reasked => "reasked",
+        }
+    }
+}
+
+/// Schema check summary for one ensemble run.
+#[derive(Debug, Clone)]
+pub struct ValidationReport {
+    pub outcome: ValidationOutcome,
+    pub reasks: u32,
+    /// `model: error` for each candidate excluded by the schema
+    pub rejected: Vec<String>,
+}
+
+/// A compiled JSON Schema for one task's output.
+pub struct OutputSchema {
+    schema: Arc<Value>,
+    validator: jsonschema::Validator,
+}
+
+impl OutputSchema {
+    pub fn compile(schema: &Value) -> std::result::Result<Self, String> {
+        let validator = jsonschema::validator_for(schema)
+            .map_err(|e| format!("invalid JSON Schema: {}", e))?;
+        Ok(Self {
+            schema: Arc::new(schema.clone()),
+            validator,
+        })
+    }
+    
+    /// The schema document, as passed to engines with constrained decoding.
+    pub fn value(&self) -> Arc<Value> {
+        self.schema.clone()
+    }
+    
+    /// Returns the normalized JSON document and whether it had to be
+    /// extracted from fences or prose, or the validation errors.
+    pub fn check(&self, content: &str) -> std::result::Result<(String, bool), String> {
+        let (json, repaired) = extract_json(content)
+            .ok_or_else(|| "output is not valid JSON".to_string())?;
+        
+        let errors: Vec<String> = self.validator
+            .iter_errors(&json)
+            .take(MAX_REPORTED_ERRORS)
+            .map(|e| {
+                let path = e.instance_path.to_string();
+                if path.is_empty() {
+                    e.to_string()
+                } else {
+                    format!("{}: {}", path, e)
+                }
+            })
+            .collect();
+        
+        if errors.is_empty() {
+            Ok((json.to_string(), repaired))
+        } else {
+            Err(errors.join("; "))
+        }
+    }
+}
+
+/// Output schemas by task, loaded from `[validation.schemas]` and
+/// `[tasks.<name>] output_schema`; the latter wins.
+pub struct SchemaRegistry {
+    schemas: HashMap<String, Arc<OutputSchema>>,
+}
+
+impl SchemaRegistry {
+    pub fn from_config(config: &ValidationConfig, tasks: &HashMap<String, TaskConfig>) -> Result<Self> {
+        let mut paths = config.schemas.clone();
+        for (task, task_config) in tasks {
+            if let Some(path) = &task_config.output_schema {
+                paths.insert(task.clone(), path.clone());
+            }
+        }
+        
+        let mut schemas = HashMap::new();
+        for (task, path) in &paths {
+            let raw = std::fs::read_to_string(path).map_err(|e| {
+                LLMPoolError::ConfigError(format!("Failed to read schema {} for task {}: {}", path, task, e))
+            })?;
+            let value: Value = serde_json::from_str(&raw).map_err(|e| {
+                LLMPoolError::ConfigError(format!("Schema {} is not valid JSON: {}", path, e))
+            })?;
+            let schema = OutputSchema::compile(&value).map_err(|e| {
+                LLMPoolError::ConfigError(format!("Schema {} for task {}: {}", path, task, e))
+            })?;
+            schemas.insert(task.clone(), Arc::new(schema));
+        }
+        
+        if !schemas.is_empty() {
+            info!("📐 Loaded output schemas for {} tasks", schemas.len());
+        }
+        Ok(Self { schemas })
+    }
+    
+    pub fn get(&self, task: &str) -> Option<Arc<OutputSchema>> {
+        self.schemas.get(task).cloned()
+    }
+}
+
+/// Parses model output as JSON, falling back to the contents of a code
+/// fence and then to the outermost object or array in the text.
+fn extract_json(content: &str) -> Option<(Value, bool)> {
+    let trimmed = content.trim();
+    if let Ok(value) = serde_json::from_str(trimmed) {
+        return Some((value, false));
+    }
+    
+    if let Some(inner) = strip_fences(trimmed) {
+        if let Ok(value) = serde_json::from_str(inner) {
+            return Some((value, true));
+        }
+    }
+    
+    let start = trimmed.find(['{', '['])?;
+    let end = trimmed.rfind(['}', ']'])?;
+    if end <= start {
+        return None;
+    }
+    serde_json::from_str(&trimmed[start..=end]).ok().map(|v| (v, true))
+}
+
+/// Body of the first ``` fence, with any language tag removed.
+fn strip_fences(text: &str) -> Option<&str> {
+    let open = text.find("```")?;
+    let rest = &text[open + 3..];
+    let body_start = rest.find('\n').map_or(0, |i| i + 1);
+    let rest = &rest[body_start..];
+    let close = rest.find("```").unwrap_or(rest.len());
+    Some(rest[..close].trim())
+}